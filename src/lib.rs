@@ -4,26 +4,46 @@ use binrw::{BinRead, BinResult, ReadOptions};
 use binrw::{BinReaderExt, BinrwNamedArgs};
 use miniz_oxide::inflate::TINFLStatus;
 use std::any::Any;
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{Read, Seek, SeekFrom};
 use std::num::NonZeroU8;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-#[derive(BinRead, Debug)]
+/// Emits a `tracing` event when the `tracing` feature is enabled, otherwise nothing.
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)*);
+    };
+}
+
+/// Enters a debug level `tracing` span until the end of the enclosing block
+/// when the `tracing` feature is enabled, otherwise nothing.
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+/// The file header. Versions 1 to 4 store 32 bit section offsets and only
+/// the id0, id1 and nam checksums, version 6 stores 64 bit offsets and the
+/// checksums of every section.
+#[derive(Debug)]
 struct IDBHeader {
-    #[br(
-    count = 4,
-    map = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned(),
-    assert(magic == "IDA0" || magic == "IDA1" || magic == "IDA2"))]
     magic: String,
-    #[br(pad_before = 0x2_u16)]
-    id0_offset: u64,
-    id1_offset: u64,
-    #[br(pad_before = 0x4_u32, assert(signature == 0xAABBCCDD))]
     signature: u32,
-    #[br(assert(version == 0x6))]
     version: u16,
+    id0_offset: u64,
+    id1_offset: u64,
     nam_offset: u64,
     seg_offset: u64,
     til_offset: u64,
@@ -32,1277 +52,4621 @@ struct IDBHeader {
     final_checksum: u32,
 }
 
-#[derive(BinRead, Debug, Default)]
-struct IDBSectionHeader {
-    compression_method: u8,
-    section_length: u64,
+impl BinRead for IDBHeader {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &ReadOptions,
+        _: Self::Args,
+    ) -> BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let magic = String::from_utf8_lossy(&reader.read_ne::<[u8; 4]>()?).into_owned();
+        if !matches!(magic.as_str(), "IDA0" | "IDA1" | "IDA2") {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!("bad IDB magic {:?}", magic),
+            });
+        }
+        let _padding = reader.read_ne::<u16>()?;
+        let offsets = reader.read_ne::<[u32; 5]>()?;
+        let signature = reader.read_ne::<u32>()?;
+        if signature != 0xAABBCCDD {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!("bad IDB signature {:#x}", signature),
+            });
+        }
+        let version = reader.read_ne::<u16>()?;
+        let mut header = Self {
+            magic,
+            signature,
+            version,
+            id0_offset: 0,
+            id1_offset: 0,
+            nam_offset: 0,
+            seg_offset: 0,
+            til_offset: 0,
+            initial_checksums: [0; 5],
+            id2_offset: 0,
+            final_checksum: 0,
+        };
+        match version {
+            1..=4 => {
+                let [id0, id1, nam, seg, til] = offsets.map(u64::from);
+                header.id0_offset = id0;
+                header.id1_offset = id1;
+                header.nam_offset = nam;
+                header.seg_offset = seg;
+                header.til_offset = til;
+                let checksums = reader.read_ne::<[u32; 3]>()?;
+                header.initial_checksums[..3].copy_from_slice(&checksums);
+            }
+            6 => {
+                let word = |low: u32, high: u32| (high as u64) << 32 | low as u64;
+                header.id0_offset = word(offsets[0], offsets[1]);
+                header.id1_offset = word(offsets[2], offsets[3]);
+                header.nam_offset = reader.read_ne()?;
+                header.seg_offset = reader.read_ne()?;
+                header.til_offset = reader.read_ne()?;
+                header.initial_checksums = reader.read_ne()?;
+                header.id2_offset = reader.read_ne()?;
+                header.final_checksum = reader.read_ne()?;
+            }
+            _ => {
+                return Err(binrw::Error::Custom {
+                    pos,
+                    err: Box::new(IDBError::UnsupportedVersion(version)),
+                })
+            }
+        }
+        Ok(header)
+    }
 }
 
-#[derive(BinRead, Debug)]
-struct ID0Section {}
-#[derive(BinRead, Debug)]
-struct ID1Section {}
-#[derive(BinRead, Debug)]
-struct NAMSection {}
-#[derive(BinRead, Debug)]
-struct SEGSection {}
+impl IDBHeader {
+    fn is_64(&self) -> bool {
+        self.magic == "IDA2"
+    }
 
-const TIL_ZIP: u32 = 0x0001;
-const TIL_MAC: u32 = 0x0002;
-const TIL_ESI: u32 = 0x0004;
-const TIL_UNI: u32 = 0x0008;
-const TIL_ORD: u32 = 0x0010;
-const TIL_ALI: u32 = 0x0020;
-const TIL_MOD: u32 = 0x0040;
-const TIL_STM: u32 = 0x0080;
-const TIL_SLD: u32 = 0x0100;
+    fn word_size(&self) -> u8 {
+        if self.is_64() {
+            8
+        } else {
+            4
+        }
+    }
 
-#[derive(BinRead, Debug, Clone)]
-#[br(import { is_u64: bool })]
-pub enum TILOrdinal {
-    #[br(pre_assert(is_u64 == false))]
-    U32(u32),
-    #[br(pre_assert(is_u64 == true))]
-    U64(u64),
+    /// The offset of a section, 0 when the database doesn't have it.
+    fn section_offset(&self, kind: IDBSectionKind) -> u64 {
+        match kind {
+            IDBSectionKind::ID0 => self.id0_offset,
+            IDBSectionKind::ID1 => self.id1_offset,
+            IDBSectionKind::NAM => self.nam_offset,
+            IDBSectionKind::SEG => self.seg_offset,
+            IDBSectionKind::TIL => self.til_offset,
+            IDBSectionKind::ID2 => self.id2_offset,
+        }
+    }
+
+    /// Each section's offset and the checksum the header records for it.
+    /// Versions 1 to 4 only checksum id0, id1 and nam.
+    fn section_checksums(&self) -> Vec<(IDBSectionKind, u64, u32)> {
+        let mut sections = vec![
+            (
+                IDBSectionKind::ID0,
+                self.id0_offset,
+                self.initial_checksums[0],
+            ),
+            (
+                IDBSectionKind::ID1,
+                self.id1_offset,
+                self.initial_checksums[1],
+            ),
+            (
+                IDBSectionKind::NAM,
+                self.nam_offset,
+                self.initial_checksums[2],
+            ),
+        ];
+        if self.version >= 6 {
+            sections.extend([
+                (
+                    IDBSectionKind::SEG,
+                    self.seg_offset,
+                    self.initial_checksums[3],
+                ),
+                (
+                    IDBSectionKind::TIL,
+                    self.til_offset,
+                    self.initial_checksums[4],
+                ),
+                (IDBSectionKind::ID2, self.id2_offset, self.final_checksum),
+            ]);
+        }
+        sections
+    }
 }
 
+/// The sections of an IDB, in header order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IDBSectionKind {
+    ID0,
+    ID1,
+    NAM,
+    SEG,
+    TIL,
+    ID2,
+}
+
+/// The CRC32 IDA uses for section checksums, the zlib one.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0_u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+/// Errors specific to the IDB container, carried in `binrw::Error::Custom`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IDBError {
+    /// The header version has a layout this crate doesn't read.
+    UnsupportedVersion(u16),
+    /// A section header names a compression method this crate can't undo.
+    UnsupportedCompression(u8),
+}
+
+impl Display for IDBError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IDBError::UnsupportedVersion(version) => {
+                write!(f, "unsupported IDB header version {}", version)
+            }
+            IDBError::UnsupportedCompression(method) => {
+                write!(f, "unsupported section compression method {}", method)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IDBError {}
+
+/// The header in front of every section's body.
+#[derive(BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IDBSectionHeader {
+    /// 0 when the body is stored as is, 2 when it is zlib compressed.
+    pub compression_method: u8,
+    /// The length of the body as stored, which follows this header.
+    pub section_length: u64,
+}
+
+/// How a section's body is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionMethod {
+    None,
+    Zlib,
+}
+
+impl TryFrom<u8> for CompressionMethod {
+    type Error = IDBError;
+
+    fn try_from(method: u8) -> Result<Self, Self::Error> {
+        match method {
+            0 => Ok(CompressionMethod::None),
+            2 => Ok(CompressionMethod::Zlib),
+            _ => Err(IDBError::UnsupportedCompression(method)),
+        }
+    }
+}
+
+impl IDBSectionHeader {
+    /// The compression method of the body.
+    pub fn compression(&self) -> Result<CompressionMethod, IDBError> {
+        CompressionMethod::try_from(self.compression_method)
+    }
+}
+
+/// Reads a section header and its body, inflating the body when it is
+/// compressed.
+fn read_section_body<R: Read + Seek>(reader: &mut R) -> BinResult<(IDBSectionHeader, Vec<u8>)> {
+    let pos = reader.stream_position()?;
+    let header = reader.read_ne::<IDBSectionHeader>()?;
+    let len = header.section_length;
+    let data = match header.compression().map_err(|err| binrw::Error::Custom {
+        pos,
+        err: Box::new(err),
+    })? {
+        CompressionMethod::None => read_bytes(reader, &ReadOptions::default(), (len,))?,
+        CompressionMethod::Zlib => {
+            let len = usize::try_from(len).map_err(|_| binrw::Error::AssertFail {
+                pos,
+                message: format!("section length {:#x} is too large", len),
+            })?;
+            inflate_zlib(reader, len, 0, false)?.0
+        }
+    };
+    Ok((header, data))
+}
+
+/// The header and body of the section at `offset` in `bytes`, `None` when
+/// either doesn't fit.
+fn section_at(bytes: &[u8], offset: u64) -> Option<(IDBSectionHeader, &[u8])> {
+    let mut cursor = binrw::io::Cursor::new(bytes.get(usize::try_from(offset).ok()?..)?);
+    let header = cursor.read_ne::<IDBSectionHeader>().ok()?;
+    let start = cursor.position() as usize;
+    let len = usize::try_from(header.section_length).ok()?;
+    let body = cursor.into_inner().get(start..start.checked_add(len)?)?;
+    Some((header, body))
+}
+
+/// The ID0 netnode b-tree. Only "B-tree v2" databases are supported.
+#[derive(Debug)]
+pub struct ID0Section {
+    header: IDBSectionHeader,
+    pub next_free_offset: u32,
+    pub page_size: u16,
+    pub root_page: u32,
+    pub record_count: u32,
+    pub page_count: u32,
+    /// Indexed by page number. The header page, free pages and pages that
+    /// failed to decode are `None`.
+    pub pages: Vec<Option<Page>>,
+}
+
+/// One key/value record of the ID0 b-tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyValueEntry {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+/// An index page entry, with the page holding the keys sorting after it.
 #[derive(Clone, Debug)]
-pub struct NullVecLenString(pub Vec<String>);
-#[derive(Clone, Default, BinRead, Debug)]
-pub struct TypeMetadata(pub u8);
-#[derive(Clone, Debug)]
-pub struct BaseTypeFlag(pub u8);
-#[derive(Clone, Debug)]
-pub struct FullTypeFlag(u8);
-#[derive(Clone, Debug)]
-pub struct TypeFlag(pub u8);
+pub struct BranchEntryPointer {
+    pub page: u32,
+    pub entry: KeyValueEntry,
+}
+
 #[derive(Clone, Debug)]
-pub struct CallingConventionFlag(u8);
+pub enum Page {
+    /// Leaf keys are prefix compressed on disk and stored whole here.
+    Leaf(Vec<KeyValueEntry>),
+    /// `preceding` is the page holding the keys sorting before the first entry.
+    Branch {
+        preceding: u32,
+        entries: Vec<BranchEntryPointer>,
+    },
+}
 
-impl CallingConventionFlag {
-    fn is_spoiled(&self) -> bool {
-        self.0 == 0xA0
+impl Page {
+    /// Parses a "B-tree v2" page. Records that don't fit in the page are
+    /// errors rather than panics.
+    pub fn parse(bytes: &[u8]) -> BinResult<Self> {
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        let preceding = cursor.read_ne::<u32>()?;
+        let count = cursor.read_ne::<u16>()?;
+        if preceding == 0 {
+            let mut entries = Vec::with_capacity(count as usize);
+            let mut last_key: &[u8] = &[];
+            for _ in 0..count {
+                let pos = cursor.position();
+                let indent = cursor.read_ne::<u16>()? as usize;
+                let _unknown = cursor.read_ne::<u16>()?;
+                let offset = cursor.read_ne::<u16>()?;
+                let (suffix, value) = Self::record(bytes, offset)?;
+                let prefix = last_key.get(..indent).ok_or(binrw::Error::AssertFail {
+                    pos,
+                    message: "ID0 key shares more bytes than the previous key has".to_string(),
+                })?;
+                let mut key = prefix.to_vec();
+                key.extend_from_slice(suffix);
+                entries.push(KeyValueEntry {
+                    key,
+                    value: value.to_vec(),
+                });
+                last_key = &entries.last().unwrap().key;
+            }
+            Ok(Page::Leaf(entries))
+        } else {
+            let mut entries = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let page = cursor.read_ne::<u32>()?;
+                let offset = cursor.read_ne::<u16>()?;
+                let (key, value) = Self::record(bytes, offset)?;
+                entries.push(BranchEntryPointer {
+                    page,
+                    entry: KeyValueEntry {
+                        key: key.to_vec(),
+                        value: value.to_vec(),
+                    },
+                });
+            }
+            Ok(Page::Branch { preceding, entries })
+        }
     }
 
-    fn is_void_arg(&self) -> bool {
-        self.0 == 0x20
-    }
+    /// The key and value of the record at `offset`, each prefixed by its u16 length.
+    fn record(bytes: &[u8], offset: u16) -> BinResult<(&[u8], &[u8])> {
+        let field = |start: usize, len: usize| {
+            bytes
+                .get(start..start + len)
+                .ok_or(binrw::Error::AssertFail {
+                    pos: start as u64,
+                    message: "ID0 record extends past the end of its page".to_string(),
+                })
+        };
+        let len = |start: usize| -> BinResult<usize> {
+            let bytes = field(start, 2)?;
+            Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+        };
 
-    fn is_special_pe(&self) -> bool {
-        self.0 == 0xD0 || self.0 == 0xE0 || self.0 == 0xF0
+        let key_start = offset as usize + 2;
+        let key = field(key_start, len(offset as usize)?)?;
+        let value_start = key_start + key.len() + 2;
+        let value = field(value_start, len(value_start - 2)?)?;
+        Ok((key, value))
     }
 }
 
-impl TypeMetadata {
-    pub fn get_base_type_flag(&self) -> BaseTypeFlag {
-        BaseTypeFlag(self.0 & 0x0F)
+impl ID0Section {
+    /// The header the section was stored with, telling whether its body was
+    /// compressed.
+    pub fn header(&self) -> IDBSectionHeader {
+        self.header
     }
 
-    pub fn get_full_type_flag(&self) -> FullTypeFlag {
-        FullTypeFlag(self.0 & (0x0F | 0x30))
+    fn page(&self, index: u32) -> Option<&Page> {
+        self.pages.get(index as usize)?.as_ref()
     }
 
-    pub fn get_type_flag(&self) -> TypeFlag {
-        TypeFlag(self.0 & 0x30)
+    /// Looks up the value stored under `key`, descending from the root page.
+    /// Keys compare bytewise.
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let mut page = self.page(self.root_page)?;
+        // A well formed tree is never deeper than it has pages
+        for _ in 0..self.pages.len() {
+            page = match page {
+                Page::Leaf(entries) => {
+                    let index = entries
+                        .binary_search_by(|entry| entry.key.as_slice().cmp(key))
+                        .ok()?;
+                    return Some(&entries[index].value);
+                }
+                Page::Branch { preceding, entries } => {
+                    match entries.binary_search_by(|pointer| pointer.entry.key.as_slice().cmp(key))
+                    {
+                        Ok(index) => return Some(&entries[index].entry.value),
+                        Err(0) => self.page(*preceding)?,
+                        Err(index) => self.page(entries[index - 1].page)?,
+                    }
+                }
+            };
+        }
+        None
     }
 
-    pub fn get_calling_convention(&self) -> CallingConventionFlag {
-        CallingConventionFlag(self.0 & 0xF0)
+    /// Every record in ascending key order.
+    pub fn entries(&self) -> impl Iterator<Item = &KeyValueEntry> {
+        EntryIter::new(self, &[])
+    }
+
+    /// The records whose key starts with `prefix`, in ascending key order.
+    /// Seeks to the first key not below `prefix` rather than walking the
+    /// whole tree, this is how the entries of a netnode are enumerated.
+    pub fn scan_prefix(&self, prefix: &[u8]) -> impl Iterator<Item = &KeyValueEntry> {
+        let prefix = prefix.to_vec();
+        EntryIter::new(self, &prefix).take_while(move |entry| entry.key.starts_with(&prefix))
     }
 }
 
-impl TypeFlag {
-    fn is_non_based(&self) -> bool {
-        self.0 == 0x10
+/// Walks the b-tree in key order from the first key not below a given one.
+/// Every page is entered at most once, so a corrupt tree whose pages point
+/// back into it ends early instead of repeating itself.
+struct EntryIter<'a> {
+    id0: &'a ID0Section,
+    /// The pages being walked, each with the position of its next entry.
+    stack: Vec<(&'a Page, usize)>,
+    visited: Vec<bool>,
+}
+
+impl<'a> EntryIter<'a> {
+    fn new(id0: &'a ID0Section, key: &[u8]) -> Self {
+        let mut iter = Self {
+            id0,
+            stack: Vec::new(),
+            visited: vec![false; id0.pages.len()],
+        };
+        iter.descend(id0.root_page, key);
+        iter
     }
 
-    pub fn is_unsigned(&self) -> bool {
-        self.0 == 0x20
+    /// Pushes the pages from `index` down to the leaf holding `key`.
+    fn descend(&mut self, mut index: u32, key: &[u8]) {
+        while let Some(page) = self.visit(index) {
+            match page {
+                Page::Leaf(entries) => {
+                    let position = entries.partition_point(|entry| entry.key.as_slice() < key);
+                    self.stack.push((page, position));
+                    return;
+                }
+                Page::Branch { preceding, entries } => {
+                    let position =
+                        entries.partition_point(|pointer| pointer.entry.key.as_slice() < key);
+                    self.stack.push((page, position));
+                    index = match position {
+                        0 => *preceding,
+                        position => entries[position - 1].page,
+                    };
+                }
+            }
+        }
     }
 
-    pub fn is_signed(&self) -> bool {
-        !self.is_unsigned()
+    fn visit(&mut self, index: u32) -> Option<&'a Page> {
+        let visited = self.visited.get_mut(index as usize)?;
+        if *visited {
+            return None;
+        }
+        *visited = true;
+        self.id0.page(index)
     }
+}
 
-    fn is_type_closure(&self) -> bool {
-        self.0 == 0x30
+impl<'a> Iterator for EntryIter<'a> {
+    type Item = &'a KeyValueEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (page, position) = self.stack.last_mut()?;
+            match page {
+                Page::Leaf(entries) => {
+                    if let Some(entry) = entries.get(*position) {
+                        *position += 1;
+                        return Some(entry);
+                    }
+                }
+                Page::Branch { entries, .. } => {
+                    if let Some(pointer) = entries.get(*position) {
+                        *position += 1;
+                        self.descend(pointer.page, &[]);
+                        return Some(&pointer.entry);
+                    }
+                }
+            }
+            self.stack.pop();
+        }
     }
 }
 
-impl FullTypeFlag {
-    fn is_enum(&self) -> bool {
-        self.0 == (0x0D | 0x20)
+/// Reads netnode values out of an [`ID0Section`]. Keys are `'.'`, the big
+/// endian node id, the tag and, for array elements, the big endian index.
+/// `word_size` is 4 for .idb and 8 for .i64 databases.
+#[derive(Clone, Copy, Debug)]
+pub struct Netnode<'a> {
+    id0: &'a ID0Section,
+    word_size: u8,
+}
+
+impl<'a> Netnode<'a> {
+    /// `None` unless `word_size` is 4 or 8.
+    pub fn new(id0: &'a ID0Section, word_size: u8) -> Option<Self> {
+        matches!(word_size, 4 | 8).then_some(Self { id0, word_size })
     }
 
-    fn is_void(&self) -> bool {
-        self.0 == (0x01 | 0x00)
+    fn key(&self, node: u64, tag: u8, index: Option<u64>) -> Vec<u8> {
+        let word = |value: u64| value.to_be_bytes()[8 - self.word_size as usize..].to_vec();
+        let mut key = vec![b'.'];
+        key.extend(word(node));
+        key.push(tag);
+        if let Some(index) = index {
+            key.extend(word(index));
+        }
+        key
     }
 
-    fn is_struct(&self) -> bool {
-        self.0 == (0x0D | 0x00)
+    /// The id of the node named `name`.
+    pub fn node_by_name(&self, name: &[u8]) -> Option<u64> {
+        let mut key = b"N".to_vec();
+        key.extend_from_slice(name);
+        let value = self.id0.get(&key)?;
+        if value.len() != self.word_size as usize {
+            return None;
+        }
+        Some(value.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64))
     }
 
-    fn is_union(&self) -> bool {
-        self.0 == (0x0D | 0x10)
+    /// The elements of the node's `'S'` array in index order.
+    pub fn supvals(&self, node: u64) -> impl Iterator<Item = (u64, &'a [u8])> + 'a {
+        let prefix = self.key(node, b'S', None);
+        let word_size = self.word_size;
+        self.id0.scan_prefix(&prefix).filter_map(move |entry| {
+            let (_, _, index) = decode_netnode_key(&entry.key, word_size)?;
+            Some((index?, entry.value.as_slice()))
+        })
     }
 
-    fn is_typedef(&self) -> bool {
-        self.0 == (0x0D | 0x30)
+    /// The raw value of an element of the `tag` array of `node`.
+    pub fn value(&self, node: u64, tag: u8, index: u64) -> Option<&'a [u8]> {
+        self.id0.get(&self.key(node, tag, Some(index)))
     }
-}
 
-impl BaseTypeFlag {
-    fn is_pointer(&self) -> bool {
-        self.0 == 0x0A
+    /// The node's name.
+    pub fn name(&self, node: u64) -> Option<&'a [u8]> {
+        self.id0.get(&self.key(node, b'N', None))
     }
 
-    fn is_function(&self) -> bool {
-        self.0 == 0x0C
+    /// An element of the node's `'A'` array. Values are little endian and
+    /// stored in as few bytes as they need.
+    pub fn altval(&self, node: u64, index: u64) -> Option<u64> {
+        let value = self.value(node, b'A', index)?;
+        if value.len() > 8 {
+            return None;
+        }
+        Some(value.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64))
     }
 
-    fn is_array(&self) -> bool {
-        self.0 == 0x0B
+    /// An element of the node's `'S'` array.
+    pub fn supval(&self, node: u64, index: u64) -> Option<&'a [u8]> {
+        self.value(node, b'S', index)
     }
 
-    fn is_bitfield(&self) -> bool {
-        self.0 == 0x0E
+    /// A single byte element of the node's `tag` array.
+    pub fn charval(&self, node: u64, index: u64, tag: u8) -> Option<u8> {
+        match self.value(node, tag, index)? {
+            [value] => Some(*value),
+            _ => None,
+        }
     }
+}
 
-    fn is_typeid_last(&self) -> bool {
-        self.0 <= 0x09
+impl BinRead for ID0Section {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &ReadOptions,
+        _: Self::Args,
+    ) -> BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let (header, data) = read_section_body(reader)?;
+        Self::from_body(header, &data, pos)
     }
+}
 
-    fn is_reserved(&self) -> bool {
-        self.0 == 0x0F
+impl ID0Section {
+    /// Decodes the b-tree from the section's inflated body, `pos` being
+    /// where the section starts for errors.
+    fn from_body(header: IDBSectionHeader, data: &[u8], pos: u64) -> BinResult<Self> {
+        let mut cursor = binrw::io::Cursor::new(data);
+        let next_free_offset = cursor.read_ne::<u32>()?;
+        let page_size = cursor.read_ne::<u16>()?;
+        let root_page = cursor.read_ne::<u32>()?;
+        let record_count = cursor.read_ne::<u32>()?;
+        let page_count = cursor.read_ne::<u32>()?;
+        let _unknown = cursor.read_ne::<u8>()?;
+        let signature = cursor.read_ne::<[u8; 9]>()?;
+        if &signature != b"B-tree v2" || page_size == 0 {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!(
+                    "unsupported ID0 b-tree {:?}",
+                    String::from_utf8_lossy(&signature)
+                ),
+            });
+        }
+
+        // Only decode the pages reachable from the root, free pages hold stale data
+        let page_size_bytes = page_size as usize;
+        let mut pages: Vec<Option<Page>> =
+            (0..data.len() / page_size_bytes).map(|_| None).collect();
+        let mut pending = vec![root_page];
+        while let Some(index) = pending.pop() {
+            let slot = match pages.get_mut(index as usize) {
+                Some(slot) if slot.is_none() && index != 0 => slot,
+                _ => continue,
+            };
+            let bytes = &data[index as usize * page_size_bytes..][..page_size_bytes];
+            match Page::parse(bytes) {
+                Ok(page) => {
+                    if let Page::Branch { preceding, entries } = &page {
+                        pending.push(*preceding);
+                        pending.extend(entries.iter().map(|pointer| pointer.page));
+                    }
+                    *slot = Some(page);
+                }
+                Err(_err) => {
+                    trace_event!(warn, page = index, error = %_err, "skipping ID0 page");
+                }
+            }
+        }
+
+        Ok(Self {
+            header,
+            next_free_offset,
+            page_size,
+            root_page,
+            record_count,
+            page_count,
+            pages,
+        })
     }
 }
 
+/// The ID1 section, one 32 bit flag word for every address of every segment.
+#[derive(Debug)]
+pub struct ID1Section {
+    pub segments: Vec<ID1Segment>,
+}
+
+/// The flag words of the addresses `start..end`.
 #[derive(Clone, Debug)]
-pub enum Types {
-    Unset(TypeMetadata),
-    Pointer(Box<Pointer>),
-    Function(Box<Function>),
-    Array(Box<Array>),
-    Typedef(Typedef),
-    Struct(Box<Struct>),
-    Union(Box<Union>),
-    Enum(Box<Enum>),
-    Bitfield(Bitfield),
-    Unknown(Vec<u8>),
+pub struct ID1Segment {
+    pub start: u64,
+    pub end: u64,
+    flags: Vec<u32>,
 }
 
-impl Default for Types {
-    fn default() -> Self {
-        Self::Unset(TypeMetadata::default())
+impl ID1Section {
+    /// The raw flag word of `ea`, `None` if it isn't inside a segment.
+    pub fn flags_at(&self, ea: u64) -> Option<u32> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| (segment.start..segment.end).contains(&ea))?;
+        segment.flags.get((ea - segment.start) as usize).copied()
     }
-}
 
-#[derive(Clone, Default, Debug)]
-pub struct DT(pub u16, u8);
-#[derive(Clone, Default, Debug)]
-pub struct DE(pub u32);
-#[derive(Clone, Default, Debug)]
-pub struct TypeAttribute(pub u16);
-#[derive(Clone, Default, Debug)]
-pub struct TAH(pub TypeAttribute);
-#[derive(Clone, Default, Debug)]
-pub struct SDACL(pub TypeAttribute);
-#[derive(Clone, Default, Debug)]
-#[binread]
-struct DTString {
-    dt: DT,
-    #[br(
-    count = dt.0,
-    map = | bytes: Vec < u8 > | String::from_utf8_lossy(& bytes).into_owned())]
-    string: String,
+    /// Every address of every segment with its flags, in segment order.
+    pub fn iter_flagged(&self) -> impl Iterator<Item = (u64, ByteFlags)> + '_ {
+        self.segments.iter().flat_map(|segment| {
+            (segment.start..)
+                .zip(segment.flags.iter())
+                .map(|(ea, &flags)| (ea, ByteFlags(flags)))
+        })
+    }
 }
-#[derive(Clone, Default, Debug)]
-#[binread]
-pub struct DTBytes {
-    pub dt: DT,
-    #[br(count = dt.0)]
-    pub bytes: Vec<u8>,
-}
-#[derive(Default, Debug)]
-struct DA {
-    nelem: u8,
-    base: u8,
-}
-
-#[derive(BinRead, Default, Clone, Debug)]
-pub struct StructMember(pub Types, pub SDACL);
-#[derive(Clone, BinRead, Default, Debug)]
-pub struct UnionMember(pub Types);
 
-#[derive(Clone, Default, Debug)]
-pub struct Ref(pub Types);
-
-pub fn serialize_dt(n: u16) -> Vec<u8> {
-    if n > 0x7FFE {
-        panic!("invalid dt");
-    }
-    let mut lo = n + 1;
-    let mut hi = n + 1;
-    let mut result: Vec<u8> = Vec::new();
-    if lo > 127 {
-        result.push((lo & 0x7F | 0x80) as u8);
-        hi = (lo >> 7) & 0xFF;
-    }
-    result.push(hi as u8);
-    result
-}
+/// Offset of the flag words in the ID1 section body, after the header page.
+const ID1_FLAGS_OFFSET: u64 = 0x2000;
 
-impl BinRead for Ref {
-    type Args = ();
+impl BinRead for ID1Section {
+    /// Whether addresses are 64 bit, true for .i64 databases.
+    type Args = (bool,);
 
     fn read_options<R: Read + Seek>(
         reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let mut bytes = reader.read_ne::<DTBytes>()?;
-        if bytes.bytes.is_empty() || bytes.bytes[0] != '=' as u8 {
-            let mut ser = serialize_dt(bytes.dt.0);
-            bytes.bytes.splice(..0, ser.drain(..));
-            bytes.bytes.insert(0, '=' as u8);
+        _: &ReadOptions,
+        (is_u64,): Self::Args,
+    ) -> BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let (_header, data) = read_section_body(reader)?;
+
+        let mut cursor = binrw::io::Cursor::new(data.as_slice());
+        let signature = cursor.read_ne::<[u8; 4]>()?;
+        let version = cursor.read_ne::<u32>()?;
+        if &signature != b"VA*\0" || version != 3 {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!("unsupported ID1 version {}", version),
+            });
+        }
+        let segment_count = cursor.read_ne::<u32>()?;
+        let _unknown = cursor.read_ne::<u32>()?;
+        let _page_count = cursor.read_ne::<u32>()?;
+        let mut bounds = Vec::new();
+        for _ in 0..segment_count {
+            let ea = |cursor: &mut binrw::io::Cursor<&[u8]>| -> BinResult<u64> {
+                if is_u64 {
+                    cursor.read_ne::<u64>()
+                } else {
+                    cursor.read_ne::<u32>().map(u64::from)
+                }
+            };
+            bounds.push((ea(&mut cursor)?, ea(&mut cursor)?));
         }
 
-        let mut cursor = binrw::io::Cursor::new(bytes.bytes);
-        Ok(Ref(cursor.read_ne::<Types>()?))
+        // The segments' flags follow each other without padding
+        cursor.seek(SeekFrom::Start(ID1_FLAGS_OFFSET))?;
+        let mut segments = Vec::with_capacity(bounds.len());
+        for (start, end) in bounds {
+            let count = end.checked_sub(start).ok_or(binrw::Error::AssertFail {
+                pos,
+                message: format!("ID1 segment {:#x} ends before it starts", start),
+            })?;
+            let len = count.checked_mul(4).ok_or(binrw::Error::AssertFail {
+                pos,
+                message: format!("ID1 segment {:#x} is too large", start),
+            })?;
+            let flags = read_bytes(&mut cursor, &ReadOptions::default(), (len,))?
+                .chunks_exact(4)
+                .map(|flags| u32::from_le_bytes([flags[0], flags[1], flags[2], flags[3]]))
+                .collect();
+            segments.push(ID1Segment { start, end, flags });
+        }
+
+        Ok(Self { segments })
     }
 }
 
-impl BinRead for DA {
-    type Args = ();
-
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let mut a = 0;
-        let mut b = 0;
-        let mut da = 0;
-        let mut base = 0;
-        let mut nelem = 0;
-        loop {
-            let mut typ = reader.read_ne::<u8>()?;
-            if typ & 0x80 == 0 {
-                reader.seek(SeekFrom::Current(-1));
-                break;
-            }
-            da = (da << 7) | typ & 0x7F;
-            b += 1;
-            if b >= 4 {
-                let mut z = reader.read_ne::<u8>()?;
-                reader.seek(SeekFrom::Current(-1));
-                if z != 0 {
-                    base = 0x10 * da | z & 0xF
-                }
-                nelem = (reader.read_ne::<u8>()? >> 4) & 7;
-                loop {
-                    let mut y = reader.read_ne::<u8>()?;
-                    reader.seek(SeekFrom::Current(-1));
-                    if (y & 0x80) == 0 {
-                        break;
-                    }
-                    reader.seek(SeekFrom::Current(1));
-                    nelem = (nelem << 7) | y & 0x7F;
-                    a += 1;
-                    if a >= 4 {
-                        return Ok(Self { nelem, base });
-                    }
-                }
-            }
-        }
-        return Ok(Self { nelem, base });
+/// Decodes an ID0 netnode key, `'.'` followed by the big endian node id, the
+/// tag and an optional big endian index, into `(node id, tag, index)`.
+/// `word_size` is 4 for .idb and 8 for .i64 databases.
+pub fn decode_netnode_key(key: &[u8], word_size: u8) -> Option<(u64, u8, Option<u64>)> {
+    let word_size = word_size as usize;
+    if key.first() != Some(&b'.') || !matches!(word_size, 4 | 8) {
+        return None;
+    }
+    let read_word = |bytes: &[u8]| bytes.iter().fold(0_u64, |acc, &b| acc << 8 | b as u64);
+    let rest = &key[1..];
+    if rest.len() < word_size + 1 {
+        return None;
+    }
+    let node = read_word(&rest[..word_size]);
+    let tag = rest[word_size];
+    let index = &rest[word_size + 1..];
+    match index.len() {
+        0 => Some((node, tag, None)),
+        len if len == word_size => Some((node, tag, Some(read_word(index)))),
+        _ => None,
     }
 }
 
-impl BinRead for TypeAttribute {
-    type Args = ();
+#[derive(BinRead, Debug)]
+struct NAMSection {}
+/// The segment table, read out of the `$ segs` netnode by
+/// [`SEGSection::from_id0`]. IDA 7 databases leave the dedicated SEG section
+/// out, and its layout in older ones isn't decoded, so a database without
+/// the netnode has no table rather than an empty one. The section's bytes
+/// are still available from [`LazyIDB::raw_section`].
+#[derive(Debug, Default)]
+pub struct SEGSection {
+    pub segments: Vec<Segment>,
+}
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let mut val: u16 = 0;
-        let mut tah: u8 = reader.read_ne()?;
-        let mut tmp = ((tah & 1) | ((tah >> 3) & 6)) + 1;
-        if tah == 0xFE || tmp == 8 {
-            if tmp == 8 {
-                val = tmp as u16;
-            }
-            let mut shift = 0;
-            loop {
-                let mut next_byte: u8 = reader.read_ne()?;
-                if next_byte == 0 {
-                    panic!("error");
-                }
-                val |= ((next_byte & 0x7F) as u16) << shift;
-                if next_byte & 0x80 == 0 {
-                    break;
-                }
-                shift += 7;
-            }
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub start: u64,
+    pub end: u64,
+    pub name: String,
+    pub class: String,
+    /// `SEGPERM_*` bits, 1 for execute, 2 for write and 4 for read.
+    pub perm: u8,
+    /// 0 for 16 bit, 1 for 32 bit and 2 for 64 bit segments.
+    pub bitness: u8,
+    /// `SEG_*` value, 1 for externs, 2 for code and 3 for data.
+    pub seg_type: u8,
+}
+
+impl Segment {
+    /// The permissions as IDA's segment list shows them, `"R-X"` for a
+    /// readable and executable segment.
+    pub fn permissions_str(&self) -> String {
+        [(4, 'R'), (2, 'W'), (1, 'X')]
+            .iter()
+            .map(|&(bit, c)| if self.perm & bit != 0 { c } else { '-' })
+            .collect()
+    }
+
+    /// The name of `seg_type`, `"CODE"` for code segments.
+    pub fn type_str(&self) -> &'static str {
+        match self.seg_type {
+            0 => "NORM",
+            1 => "XTRN",
+            2 => "CODE",
+            3 => "DATA",
+            4 => "IMP",
+            6 => "GRP",
+            7 => "NULL",
+            8 => "UNDF",
+            9 => "BSS",
+            10 => "ABSSYM",
+            11 => "COMM",
+            12 => "IMEM",
+            _ => "UNKNOWN",
         }
-        let mut unk = Vec::new();
-        if (val & 0x0010) > 0 {
-            val = reader.read_ne::<DT>()?.0;
-            for _ in 0..val {
-                let string = reader.read_ne::<DTString>()?;
-                let another_de = reader.read_ne::<DT>()?;
-                reader.seek(SeekFrom::Current(another_de.0 as i64));
-                unk.push(string.string);
+    }
+}
+
+impl SEGSection {
+    /// Reads the segment table out of the `$ segs` netnode, with names from
+    /// `$ segstrings`. `None` if the database has no segment table.
+    pub fn from_id0(id0: &ID0Section, word_size: u8) -> Option<Self> {
+        let netnode = Netnode::new(id0, word_size)?;
+        let mut strings = HashMap::new();
+        if let Some(node) = netnode.node_by_name(b"$ segstrings") {
+            for (_, value) in netnode.supvals(node) {
+                read_segment_strings(value, &mut strings).ok()?;
             }
         }
-        return Ok(TypeAttribute(val));
+        let node = netnode.node_by_name(b"$ segs")?;
+        let mut segments = netnode
+            .supvals(node)
+            .map(|(_, value)| read_segment(value, word_size, &strings))
+            .collect::<BinResult<Vec<_>>>()
+            .ok()?;
+        segments.sort_by_key(|segment| segment.start);
+        Some(Self { segments })
     }
-}
 
-impl BinRead for SDACL {
-    type Args = ();
+    /// The segment containing `ea`.
+    pub fn segment_at(&self, ea: u64) -> Option<&Segment> {
+        let index = self.segments.partition_point(|segment| segment.start <= ea);
+        let segment = self.segments.get(index.checked_sub(1)?)?;
+        (ea < segment.end).then_some(segment)
+    }
+}
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let sdacl: u8 = reader.read_ne()?;
-        if ((sdacl & !0x30) ^ 0xC0) <= 0x01 {
-            reader.seek(SeekFrom::Current(-1));
-            Ok(SDACL(reader.read_ne::<TypeAttribute>()?))
-        } else {
-            reader.seek(SeekFrom::Current(-1));
-            Ok(SDACL::default())
-        }
+/// Reads one `$ segstrings` block, the first and one past the last string id
+/// followed by the length prefixed strings.
+fn read_segment_strings(bytes: &[u8], strings: &mut HashMap<u32, String>) -> BinResult<()> {
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let PackedDD(first) = cursor.read_ne()?;
+    let PackedDD(end) = cursor.read_ne()?;
+    for id in first..end {
+        let PackedDD(len) = cursor.read_ne()?;
+        let name = read_bytes(&mut cursor, &ReadOptions::default(), (len as u64,))?;
+        strings.insert(id, String::from_utf8_lossy(&name).into_owned());
     }
+    Ok(())
 }
 
-impl BinRead for TAH {
-    type Args = ();
+/// Reads a packed `segment_t`, the fields IDA writes before the selector and
+/// the default segment registers.
+fn read_segment(bytes: &[u8], word_size: u8, strings: &HashMap<u32, String>) -> BinResult<Segment> {
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let mut ea = || read_packed_ea(&mut cursor, word_size);
+    let start = ea()?;
+    let size = ea()?;
+    let name = ea()?;
+    let class = ea()?;
+    let _orgbase = ea()?;
+    let mut dd = || cursor.read_ne::<PackedDD>().map(|PackedDD(value)| value);
+    let _flags = dd()?;
+    let _align = dd()?;
+    let _comb = dd()?;
+    let perm = dd()?;
+    let bitness = dd()?;
+    let seg_type = dd()?;
+    let string = |id: u64| {
+        u32::try_from(id)
+            .ok()
+            .and_then(|id| strings.get(&id))
+            .cloned()
+            .unwrap_or_default()
+    };
+    Ok(Segment {
+        start,
+        end: start.wrapping_add(size),
+        name: string(name),
+        class: string(class),
+        perm: perm as u8,
+        bitness: bitness as u8,
+        seg_type: seg_type as u8,
+    })
+}
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let tah: u8 = reader.read_ne()?;
-        if tah == 0xFE {
-            reader.seek(SeekFrom::Current(-1));
-            Ok(TAH(reader.read_ne::<TypeAttribute>()?))
-        } else {
-            reader.seek(SeekFrom::Current(-1));
-            Ok(TAH::default())
-        }
+/// Reads a packed address, the low and high halves as separate packed
+/// values in 64 bit databases.
+fn read_packed_ea<R: Read + Seek>(reader: &mut R, word_size: u8) -> BinResult<u64> {
+    let PackedDD(low) = reader.read_ne()?;
+    if word_size == 8 {
+        let PackedDD(high) = reader.read_ne()?;
+        Ok((high as u64) << 32 | low as u64)
+    } else {
+        Ok(low as u64)
     }
 }
 
-impl BinRead for DE {
-    type Args = ();
+/// A relocation IDA applied to the image, from the `$ fixups` netnode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fixup {
+    /// The address of the patched bytes.
+    pub ea: u64,
+    /// The fixup type byte as stored, `FIXUP_*` with its flag bits.
+    pub kind: u8,
+    /// The address the fixup refers to, its base plus its offset.
+    pub target: u64,
+}
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let mut val: u32 = 0;
-        loop {
-            let mut hi = val << 6;
-            let mut b: u8 = reader.read_ne()?;
-            let mut sign = b & 0x80;
-            if sign == 0 {
-                let mut lo = b & 0x3F;
-                val = (lo as u32) | hi;
-                break;
-            } else {
-                let mut lo = 2 * hi;
-                hi = (b as u32) & 0x7F;
-                val = lo | hi;
-            }
-        }
-        return Ok(DE(val));
+impl ID0Section {
+    /// The fixups of the `$ fixups` netnode in address order, empty if the
+    /// database has none. Entries that don't decode are skipped.
+    pub fn fixups(&self) -> Vec<Fixup> {
+        // Node ids are stored in a word, which gives the database's word size
+        let word_size = match self.get(b"N$ fixups") {
+            Some(value) => value.len() as u8,
+            None => return Vec::new(),
+        };
+        let netnode = match Netnode::new(self, word_size) {
+            Some(netnode) => netnode,
+            None => return Vec::new(),
+        };
+        let node = match netnode.node_by_name(b"$ fixups") {
+            Some(node) => node,
+            None => return Vec::new(),
+        };
+        netnode
+            .supvals(node)
+            .filter_map(|(ea, value)| {
+                let fixup = read_fixup(ea, value, word_size);
+                if let Err(_err) = &fixup {
+                    trace_event!(warn, ea, error = %_err, "skipping fixup");
+                }
+                fixup.ok()
+            })
+            .collect()
     }
 }
 
-impl BinRead for DT {
-    type Args = ();
+/// Reads a `$ fixups` supval, the type byte, packed flags, base and offset.
+fn read_fixup(ea: u64, bytes: &[u8], word_size: u8) -> BinResult<Fixup> {
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let kind = cursor.read_ne::<u8>()?;
+    let PackedDD(_flags) = cursor.read_ne()?;
+    let base = read_packed_ea(&mut cursor, word_size)?;
+    let offset = read_packed_ea(&mut cursor, word_size)?;
+    Ok(Fixup {
+        ea,
+        kind,
+        target: base.wrapping_add(offset),
+    })
+}
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let mut val__u8 = reader.read_ne::<u8>()?;
-        let mut val = val__u8 as u16;
-        let mut SEG = 1;
-        if (val__u8 & 0x80) > 0 {
-            let intermediate = reader.read_ne::<u8>()? as u16;
-            val = val & 0x7F | intermediate << 7;
-            SEG = 2;
-        }
-        return Ok(DT(val - 1, SEG));
+const MS_CLS: u32 = 0x00000600;
+const FF_CODE: u32 = 0x00000600;
+const FF_DATA: u32 = 0x00000400;
+const FF_TAIL: u32 = 0x00000200;
+const FF_UNK: u32 = 0x00000000;
+const DT_TYPE: u32 = 0xF0000000;
+const FF_COMM: u32 = 0x00000800;
+const FF_NAME: u32 = 0x00004000;
 
-        // let mut val: u8 = reader.read_ne()?;
-        // if (val & 0x80) == 1 {
-        //     val = val & 0x7f;
-        //     let other: u8 = reader.read_ne()?;
-        //     Ok(DT(((val as u16) | (other as u16) << 7) - 1, 2))
-        // } else {
-        //     val = val.overflowing_sub(1).0;
-        //     Ok(DT((val) as u16, 1))
-        // }
-    }
+/// True if the ID1 flags mark the start of an instruction, see
+/// [`ByteFlags::is_code`].
+pub fn is_code(flags: u32) -> bool {
+    ByteFlags(flags).is_code()
 }
 
-#[derive(Clone, Default, Debug)]
-// #[binread]
-pub struct Pointer {
-    pub metadata: TypeMetadata,
-    // #[br(if(metadata.get_type_flag().is_type_closure()))]
-    // closure_decision: u8,
-    // #[br(if(metadata.get_type_flag().is_type_closure() && closure_decision == 0xFF))]
-    pub closure: Option<Types>,
-    // #[br(if(metadata.get_type_flag().is_type_closure() && closure_decision != 0xFF))]
-    pub based_ptr_size: u8,
-    pub tah: TAH,
-    pub typ: Types,
+/// True if the ID1 flags mark the start of a data item, see
+/// [`ByteFlags::is_data`].
+pub fn is_data(flags: u32) -> bool {
+    ByteFlags(flags).is_data()
 }
 
-impl BinRead for Pointer {
-    type Args = ();
+/// True if the ID1 flags mark a byte inside an instruction or data item, see
+/// [`ByteFlags::is_tail`].
+pub fn is_tail(flags: u32) -> bool {
+    ByteFlags(flags).is_tail()
+}
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let mut ptr = Pointer::default();
-        ptr.metadata = metadata;
-        if ptr.metadata.get_type_flag().is_type_closure() {
-            if reader.read_ne::<u8>()? == 0xFF {
-                ptr.closure = Some(reader.read_ne::<Types>()?);
-            } else {
-                ptr.closure = None;
-                ptr.based_ptr_size = reader.read_ne::<u8>()?;
-            }
-        }
-        ptr.tah = reader.read_ne()?;
-        ptr.typ = reader.read_ne()?;
-        Ok(ptr)
-    }
+/// True if the ID1 flags mark an unexplored byte, see
+/// [`ByteFlags::is_unknown`].
+pub fn is_unknown(flags: u32) -> bool {
+    ByteFlags(flags).is_unknown()
 }
 
-#[derive(Clone, Default, Debug)]
-pub struct FuncArgs(pub Types);
-#[derive(Clone, Default, Debug)]
-pub struct Function {
-    metadata: TypeMetadata,
-    cc: TypeMetadata,
-    pub ret: Types,
-    pub args: Vec<FuncArgs>,
+/// Size in bytes of the data item described by the ID1 flags, see
+/// [`ByteFlags::data_size`].
+pub fn data_size(flags: u32) -> Option<u64> {
+    ByteFlags(flags).data_size()
 }
-impl BinRead for Function {
-    type Args = ();
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let mut flags = 0;
-        flags |= 4 * metadata.get_type_flag().0;
+/// An ID1 flag word.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteFlags(pub u32);
 
-        let mut cm = reader.read_ne::<TypeMetadata>()?;
-        if cm.get_calling_convention().is_spoiled() {
-            loop {
-                if !cm.get_calling_convention().is_spoiled() {
-                    break;
-                }
+impl ByteFlags {
+    /// True at the start of an instruction.
+    pub fn is_code(&self) -> bool {
+        self.0 & MS_CLS == FF_CODE
+    }
 
-                reader.seek(SeekFrom::Current(1));
-                let mut nspoiled = cm.0 & !0xf0;
-                let mut f = 0_u8;
-                if nspoiled == 15 {
-                    f = 2 * (reader.read_ne::<u8>()? & 0x1F)
-                }
+    /// True at the start of a data item.
+    pub fn is_data(&self) -> bool {
+        self.0 & MS_CLS == FF_DATA
+    }
 
-                cm = reader.read_ne::<TypeMetadata>()?;
-                reader.seek(SeekFrom::Current(-1));
-                flags |= f;
-            }
-        }
-        reader.seek(SeekFrom::Current(-1));
-        let cc = reader.read_ne::<TypeMetadata>()?;
-        let tah = reader.read_ne::<TAH>()?;
-        let ret = reader.read_ne::<Types>()?;
-        if cc.get_calling_convention().is_special_pe() {
-            match &ret {
-                Types::Unset(mdata) => {
-                    if !mdata.get_full_type_flag().is_void() {
-                        panic!("Special PE unhandled");
-                    }
-                }
-                _ => {}
-            }
-        }
+    /// True for a byte inside an instruction or data item.
+    pub fn is_tail(&self) -> bool {
+        self.0 & MS_CLS == FF_TAIL
+    }
+
+    /// True for an unexplored byte.
+    pub fn is_unknown(&self) -> bool {
+        self.0 & MS_CLS == FF_UNK
+    }
+
+    pub fn has_name(&self) -> bool {
+        self.0 & FF_NAME != 0
+    }
+
+    pub fn has_comment(&self) -> bool {
+        self.0 & FF_COMM != 0
+    }
+
+    /// Size in bytes of the data item. `None` for non-data bytes and for
+    /// types whose size isn't fixed (strings, structs, ...).
+    pub fn data_size(&self) -> Option<u64> {
+        if !self.is_data() {
+            return None;
+        }
+        match self.0 & DT_TYPE {
+            0x00000000 => Some(1),  // FF_BYTE
+            0x10000000 => Some(2),  // FF_WORD
+            0x20000000 => Some(4),  // FF_DWORD
+            0x30000000 => Some(8),  // FF_QWORD
+            0x40000000 => Some(10), // FF_TBYTE
+            0x70000000 => Some(16), // FF_OWORD
+            0x80000000 => Some(4),  // FF_FLOAT
+            0x90000000 => Some(8),  // FF_DOUBLE
+            0xE0000000 => Some(32), // FF_YWORD
+            0xF0000000 => Some(64), // FF_ZWORD
+            _ => None,
+        }
+    }
+}
+
+/// The buckets are zlib compressed.
+const TIL_ZIP: u32 = 0x0001;
+/// The buckets have macro definitions.
+const TIL_MAC: u32 = 0x0002;
+/// The header has the extended sizeof info, short, long and long long.
+const TIL_ESI: u32 = 0x0004;
+/// Universal til, for any compiler.
+const TIL_UNI: u32 = 0x0008;
+/// The types bucket is preceded by the number of ordinals.
+const TIL_ORD: u32 = 0x0010;
+/// Type aliases are present.
+const TIL_ALI: u32 = 0x0020;
+/// The til was modified and should be saved.
+const TIL_MOD: u32 = 0x0040;
+/// Extra streams follow the buckets.
+const TIL_STM: u32 = 0x0080;
+/// The header has sizeof(long double).
+const TIL_SLD: u32 = 0x0100;
+
+#[derive(BinRead, Debug, Clone)]
+#[br(import { is_u64: bool })]
+pub enum TILOrdinal {
+    #[br(pre_assert(is_u64 == false))]
+    U32(u32),
+    #[br(pre_assert(is_u64 == true))]
+    U64(u64),
+}
+
+impl TILOrdinal {
+    pub fn value(&self) -> u64 {
+        match self {
+            TILOrdinal::U32(ordinal) => *ordinal as u64,
+            TILOrdinal::U64(ordinal) => *ordinal,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NullVecLenString(pub Vec<String>);
+#[derive(Clone, Default, BinRead, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TypeMetadata(pub u8);
+#[derive(Clone, Debug)]
+pub struct BaseTypeFlag(pub u8);
+#[derive(Clone, Debug)]
+pub struct FullTypeFlag(u8);
+#[derive(Clone, Debug)]
+pub struct TypeFlag(pub u8);
+#[derive(Clone, Debug)]
+pub struct CallingConventionFlag(u8);
+
+/// A function's calling convention. The void argument and ellipsis flavours
+/// of cdecl are both `Cdecl`, see [`Function::is_variadic`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CallingConvention {
+    Invalid,
+    Unknown,
+    Cdecl,
+    Stdcall,
+    Pascal,
+    Fastcall,
+    Thiscall,
+    Manual,
+    Golang,
+    /// `__usercall`, with or without an ellipsis.
+    Usercall,
+    /// `__userpurge`, a `__usercall` where the callee cleans the stack.
+    Userpurge,
+}
+
+/// The code and data pointer model of a `cm` byte, bits 0x0C.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryModel {
+    /// Near code and near data, CM_M_NN.
+    Small,
+    /// Far code and far data, CM_M_FF.
+    Large,
+    /// Near code and far data, CM_M_NF.
+    Compact,
+    /// Far code and near data, CM_M_FN.
+    Medium,
+}
+
+impl MemoryModel {
+    fn from_cm(cm: u8) -> Self {
+        match cm & 0x0C {
+            0x04 => MemoryModel::Large,
+            0x08 => MemoryModel::Compact,
+            0x0C => MemoryModel::Medium,
+            _ => MemoryModel::Small,
+        }
+    }
+}
+
+impl CallingConventionFlag {
+    pub fn convention(&self) -> CallingConvention {
+        match self.0 {
+            0x10 => CallingConvention::Unknown,
+            0x20 | 0x30 | 0x40 => CallingConvention::Cdecl,
+            0x50 => CallingConvention::Stdcall,
+            0x60 => CallingConvention::Pascal,
+            0x70 => CallingConvention::Fastcall,
+            0x80 => CallingConvention::Thiscall,
+            0x90 => CallingConvention::Manual,
+            0xB0 => CallingConvention::Golang,
+            0xD0 | 0xF0 => CallingConvention::Usercall,
+            0xE0 => CallingConvention::Userpurge,
+            _ => CallingConvention::Invalid,
+        }
+    }
+
+    fn is_spoiled(&self) -> bool {
+        self.0 == 0xA0
+    }
+
+    fn is_void_arg(&self) -> bool {
+        self.0 == 0x20
+    }
+
+    fn is_special_pe(&self) -> bool {
+        self.0 == 0xD0 || self.0 == 0xE0 || self.0 == 0xF0
+    }
+
+    fn is_ellipsis(&self) -> bool {
+        self.0 == 0x40 || self.0 == 0xD0
+    }
+}
+
+impl TypeMetadata {
+    pub fn get_base_type_flag(&self) -> BaseTypeFlag {
+        BaseTypeFlag(self.0 & 0x0F)
+    }
+
+    /// The base type and its BTMT_* bits, without the const/volatile modifiers.
+    pub fn get_full_type_flag(&self) -> FullTypeFlag {
+        FullTypeFlag(self.0 & (0x0F | 0x30))
+    }
+
+    pub fn get_type_flag(&self) -> TypeFlag {
+        TypeFlag(self.0 & 0x30)
+    }
+
+    pub fn get_calling_convention(&self) -> CallingConventionFlag {
+        CallingConventionFlag(self.0 & 0xF0)
+    }
+}
+
+impl TypeFlag {
+    fn is_non_based(&self) -> bool {
+        self.0 == 0x10
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        self.0 == 0x20
+    }
+
+    pub fn is_signed(&self) -> bool {
+        !self.is_unsigned()
+    }
+
+    fn is_type_closure(&self) -> bool {
+        self.0 == 0x30
+    }
+}
+
+impl FullTypeFlag {
+    fn is_enum(&self) -> bool {
+        self.0 == (0x0D | 0x20)
+    }
+
+    fn is_void(&self) -> bool {
+        self.0 == (0x01 | 0x00)
+    }
+
+    fn is_struct(&self) -> bool {
+        self.0 == (0x0D | 0x00)
+    }
+
+    fn is_union(&self) -> bool {
+        self.0 == (0x0D | 0x10)
+    }
+
+    fn is_typedef(&self) -> bool {
+        self.0 == (0x0D | 0x30)
+    }
+}
+
+impl BaseTypeFlag {
+    fn is_pointer(&self) -> bool {
+        self.0 == 0x0A
+    }
+
+    fn is_function(&self) -> bool {
+        self.0 == 0x0C
+    }
+
+    fn is_array(&self) -> bool {
+        self.0 == 0x0B
+    }
+
+    fn is_bitfield(&self) -> bool {
+        self.0 == 0x0E
+    }
+
+    fn is_typeid_last(&self) -> bool {
+        self.0 <= 0x09
+    }
+
+    fn is_reserved(&self) -> bool {
+        self.0 == 0x0F
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Types {
+    Unset(TypeMetadata),
+    Pointer(Box<Pointer>),
+    Function(Box<Function>),
+    Array(Box<Array>),
+    Typedef(Typedef),
+    Struct(Box<Struct>),
+    Union(Box<Union>),
+    Enum(Box<Enum>),
+    Bitfield(Bitfield),
+    /// BT_RESERVED, the marker IDA uses for a partial type whose definition
+    /// is incomplete. Only the type byte is stored.
+    Reserved(TypeMetadata),
+    Unknown(Vec<u8>),
+}
+
+/// The kind of a [`Types`] value without its contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    Primitive,
+    Pointer,
+    Function,
+    Array,
+    Typedef,
+    Struct,
+    Union,
+    Enum,
+    Bitfield,
+    Reserved,
+    Unknown,
+}
+
+impl Types {
+    pub fn kind(&self) -> TypeKind {
+        match self {
+            Types::Unset(_) => TypeKind::Primitive,
+            Types::Pointer(_) => TypeKind::Pointer,
+            Types::Function(_) => TypeKind::Function,
+            Types::Array(_) => TypeKind::Array,
+            Types::Typedef(_) => TypeKind::Typedef,
+            Types::Struct(_) => TypeKind::Struct,
+            Types::Union(_) => TypeKind::Union,
+            Types::Enum(_) => TypeKind::Enum,
+            Types::Bitfield(_) => TypeKind::Bitfield,
+            Types::Reserved(_) => TypeKind::Reserved,
+            Types::Unknown(_) => TypeKind::Unknown,
+        }
+    }
+
+    /// The size of the type in bytes, using the integer and pointer sizes of
+    /// `sec` and resolving typedefs through it. Returns `None` for functions,
+    /// `void` and types that can't be resolved.
+    pub fn size(&self, sec: &TILSection) -> Option<u64> {
+        sec.size_align(self, 0).map(|(size, _)| size)
+    }
+}
+
+impl Default for Types {
+    fn default() -> Self {
+        Self::Unset(TypeMetadata::default())
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DT(pub u16, u8);
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DE(pub u32);
+/// A u32 as IDA packs it into netnode values, one to five bytes.
+#[derive(Clone, Copy, Default, Debug)]
+struct PackedDD(u32);
+/// Attribute flags, and the key/value pairs of `__attribute__` strings when
+/// flag 0x10 is set.
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TypeAttribute(pub u16, pub Vec<(String, Vec<u8>)>);
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TAH(pub TypeAttribute);
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SDACL(pub TypeAttribute);
+#[derive(Clone, Default, Debug)]
+#[binread]
+struct DTString {
+    dt: DT,
+    #[br(
+    count = dt.0,
+    map = | bytes: Vec < u8 > | String::from_utf8_lossy(& bytes).into_owned())]
+    string: String,
+}
+#[derive(Clone, Default, Debug)]
+#[binread]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DTBytes {
+    pub dt: DT,
+    #[br(count = dt.0)]
+    pub bytes: Vec<u8>,
+}
+#[derive(Default, Debug)]
+struct DA {
+    nelem: u8,
+    base: u8,
+}
+
+#[derive(BinRead, Default, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[br(import(depth: TypeDepth))]
+pub struct StructMember(#[br(args(0, depth))] pub Types, pub SDACL);
+#[derive(Clone, BinRead, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[br(import(depth: TypeDepth))]
+pub struct UnionMember(#[br(args(0, depth))] pub Types);
+
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Ref(pub Types);
+
+/// How deep type readers nest by default, see [`IdbParser::max_recursion`].
+const DEFAULT_MAX_RECURSION: u32 = 64;
+
+/// The nesting left to the type readers, passed down through their args so
+/// that a type nested without end fails instead of overflowing the stack.
+/// The default allows 64 levels.
+#[derive(Clone, Copy, Debug)]
+pub struct TypeDepth {
+    remaining: u32,
+    max: u32,
+}
+
+impl Default for TypeDepth {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RECURSION)
+    }
+}
+
+impl TypeDepth {
+    /// Allows types nested `max` levels deep, a lone basic type being one.
+    pub fn new(max: u32) -> Self {
+        Self {
+            remaining: max,
+            max,
+        }
+    }
+
+    /// Takes one level for the type starting at `pos`.
+    fn nest(self, pos: u64) -> BinResult<Self> {
+        match self.remaining.checked_sub(1) {
+            Some(remaining) => Ok(Self { remaining, ..self }),
+            None => Err(binrw::Error::AssertFail {
+                pos,
+                message: format!("type nests deeper than {} levels", self.max),
+            }),
+        }
+    }
+}
+
+pub fn serialize_dt(n: u16) -> Vec<u8> {
+    if n > 0x7FFE {
+        panic!("invalid dt");
+    }
+    let mut lo = n + 1;
+    let mut hi = n + 1;
+    let mut result: Vec<u8> = Vec::new();
+    if lo > 127 {
+        result.push((lo & 0x7F | 0x80) as u8);
+        hi = (lo >> 7) & 0xFF;
+    }
+    result.push(hi as u8);
+    result
+}
+
+impl BinRead for Ref {
+    type Args = (TypeDepth,);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        (depth,): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let mut bytes = reader.read_ne::<DTBytes>()?;
+        if bytes.bytes.is_empty() {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: "empty type reference".to_string(),
+            });
+        }
+        if bytes.bytes[0] != b'=' {
+            let mut ser = serialize_dt(bytes.dt.0);
+            bytes.bytes.splice(..0, ser.drain(..));
+            bytes.bytes.insert(0, '=' as u8);
+        }
+
+        let mut cursor = binrw::io::Cursor::new(bytes.bytes);
+        Ok(Ref(cursor.read_ne_args((0, depth))?))
+    }
+}
+
+impl BinRead for DA {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let mut a = 0;
+        let mut b = 0;
+        let mut da = 0;
+        let mut base = 0;
+        let mut nelem = 0;
+        loop {
+            let typ = reader.read_ne::<u8>()?;
+            if typ & 0x80 == 0 {
+                reader.seek(SeekFrom::Current(-1))?;
+                break;
+            }
+            da = (da << 7) | typ & 0x7F;
+            b += 1;
+            if b >= 4 {
+                let z = reader.read_ne::<u8>()?;
+                if z != 0 {
+                    base = 0x10 * da | z & 0xF
+                }
+                nelem = (z >> 4) & 7;
+                loop {
+                    let y = reader.read_ne::<u8>()?;
+                    if (y & 0x80) == 0 {
+                        reader.seek(SeekFrom::Current(-1))?;
+                        break;
+                    }
+                    nelem = (nelem << 7) | y & 0x7F;
+                    a += 1;
+                    if a >= 4 {
+                        return Ok(Self { nelem, base });
+                    }
+                }
+            }
+        }
+        Ok(Self { nelem, base })
+    }
+}
+
+impl BinRead for TypeAttribute {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let mut val: u16 = 0;
+        let mut tah: u8 = reader.read_ne()?;
+        let mut tmp = ((tah & 1) | ((tah >> 3) & 6)) + 1;
+        if tah == 0xFE || tmp == 8 {
+            if tmp == 8 {
+                val = tmp as u16;
+            }
+            let mut shift = 0;
+            loop {
+                let mut next_byte: u8 = reader.read_ne()?;
+                // A u16 takes at most three 7 bit groups
+                if next_byte == 0 || shift > 14 {
+                    return Err(binrw::Error::AssertFail {
+                        pos,
+                        message: "invalid type attribute".to_string(),
+                    });
+                }
+                val |= ((next_byte & 0x7F) as u16) << shift;
+                if next_byte & 0x80 == 0 {
+                    break;
+                }
+                shift += 7;
+            }
+        }
+        let mut strings = Vec::new();
+        if (val & 0x0010) > 0 {
+            let count = reader.read_ne::<DT>()?.0;
+            for _ in 0..count {
+                let key = reader.read_ne::<DTString>()?.string;
+                let value = reader.read_ne::<DTBytes>()?.bytes;
+                strings.push((key, value));
+            }
+        }
+        Ok(TypeAttribute(val, strings))
+    }
+}
+
+/// Returns the next byte without consuming it, or `None` at the end of the stream
+/// so optional trailing attributes can be absent.
+fn peek_u8<R: Read + Seek>(reader: &mut R) -> BinResult<Option<u8>> {
+    let mut byte = [0_u8; 1];
+    if reader.read(&mut byte)? == 0 {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Current(-1))?;
+    Ok(Some(byte[0]))
+}
+
+impl BinRead for SDACL {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        match peek_u8(reader)? {
+            Some(sdacl) if ((sdacl & !0x30) ^ 0xC0) <= 0x01 => {
+                Ok(SDACL(reader.read_ne::<TypeAttribute>()?))
+            }
+            _ => Ok(SDACL::default()),
+        }
+    }
+}
+
+impl BinRead for TAH {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        match peek_u8(reader)? {
+            Some(0xFE) => Ok(TAH(reader.read_ne::<TypeAttribute>()?)),
+            _ => Ok(TAH::default()),
+        }
+    }
+}
+
+impl BinRead for DE {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        // Continuation bytes carry 7 bits each and the final byte 6, so a u32
+        // never needs more than 5 bytes.
+        let mut val: u32 = 0;
+        for _ in 0..5 {
+            let hi = val << 6;
+            let b: u8 = reader.read_ne()?;
+            if b & 0x80 == 0 {
+                return Ok(DE((b & 0x3F) as u32 | hi));
+            }
+            val = (hi << 1) | (b & 0x7F) as u32;
+        }
+        Err(binrw::Error::AssertFail {
+            pos,
+            message: "DE value is longer than 5 bytes".to_string(),
+        })
+    }
+}
+
+impl BinRead for PackedDD {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let b: u8 = reader.read_ne()?;
+        let value = if b & 0x80 == 0 {
+            b as u32
+        } else if b & 0xC0 == 0x80 {
+            ((b & 0x3F) as u32) << 8 | reader.read_ne::<u8>()? as u32
+        } else if b & 0xE0 == 0xC0 {
+            let [b1, b2, b3] = reader.read_ne::<[u8; 3]>()?;
+            u32::from_be_bytes([b & 0x1F, b1, b2, b3])
+        } else {
+            reader.read_be::<u32>()?
+        };
+        Ok(PackedDD(value))
+    }
+}
+
+impl BinRead for DT {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let mut val__u8 = reader.read_ne::<u8>()?;
+        let mut val = val__u8 as u16;
+        let mut SEG = 1;
+        if (val__u8 & 0x80) > 0 {
+            let intermediate = reader.read_ne::<u8>()? as u16;
+            val = val & 0x7F | intermediate << 7;
+            SEG = 2;
+        }
+        // Values are stored plus one, a zero never occurs in valid data
+        let val = val.checked_sub(1).ok_or(binrw::Error::AssertFail {
+            pos,
+            message: "DT value of zero".to_string(),
+        })?;
+        Ok(DT(val, SEG))
+
+        // let mut val: u8 = reader.read_ne()?;
+        // if (val & 0x80) == 1 {
+        //     val = val & 0x7f;
+        //     let other: u8 = reader.read_ne()?;
+        //     Ok(DT(((val as u16) | (other as u16) << 7) - 1, 2))
+        // } else {
+        //     val = val.overflowing_sub(1).0;
+        //     Ok(DT((val) as u16, 1))
+        // }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+// #[binread]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Pointer {
+    pub metadata: TypeMetadata,
+    // #[br(if(metadata.get_type_flag().is_type_closure()))]
+    // closure_decision: u8,
+    // #[br(if(metadata.get_type_flag().is_type_closure() && closure_decision == 0xFF))]
+    pub closure: Option<Types>,
+    // #[br(if(metadata.get_type_flag().is_type_closure() && closure_decision != 0xFF))]
+    pub based_ptr_size: u8,
+    pub tah: TAH,
+    pub typ: Types,
+}
+
+impl BinRead for Pointer {
+    type Args = (TypeDepth,);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        (depth,): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        let mut ptr = Pointer::default();
+        ptr.metadata = metadata;
+        if ptr.metadata.get_type_flag().is_type_closure() {
+            if reader.read_ne::<u8>()? == 0xFF {
+                ptr.closure = Some(reader.read_ne_args((0, depth))?);
+            } else {
+                ptr.closure = None;
+                ptr.based_ptr_size = reader.read_ne::<u8>()?;
+            }
+        }
+        ptr.tah = reader.read_ne()?;
+        ptr.typ = reader.read_ne_args((0, depth))?;
+        Ok(ptr)
+    }
+}
+
+/// Where a special calling convention (`__usercall` and friends) passes an
+/// argument or return value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ArgLoc {
+    None,
+    /// Stack offset.
+    Stack(u32),
+    /// Register number, with the offset into the register in the high 16 bits.
+    Reg1(u32),
+    /// Register pair, the second register in the high 16 bits.
+    Reg2(u32),
+    /// Relative to a register.
+    RRel {
+        reg: u16,
+        offset: u32,
+    },
+    /// Global address.
+    Static(u32),
+}
+
+const ALOC_NONE: u16 = 0;
+const ALOC_STACK: u16 = 1;
+const ALOC_DIST: u16 = 2;
+const ALOC_REG1: u16 = 3;
+const ALOC_REG2: u16 = 4;
+const ALOC_RREL: u16 = 5;
+const ALOC_STATIC: u16 = 6;
+
+impl BinRead for ArgLoc {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let t = reader.read_ne::<u8>()?;
+        if t != 0xFF {
+            // Old single byte register/stack encoding
+            let b = t & 0x7F;
+            return Ok(if t <= 0x80 {
+                match b {
+                    0 => ArgLoc::Stack(0),
+                    _ => ArgLoc::Reg1((b - 1) as u32),
+                }
+            } else {
+                match reader.read_ne::<u8>()? {
+                    0 => ArgLoc::None,
+                    c => ArgLoc::Reg2(b as u32 | ((c - 1) as u32) << 16),
+                }
+            });
+        }
+
+        let typ = reader.read_ne::<DT>()?.0;
+        match typ & 0xF {
+            ALOC_NONE => Ok(ArgLoc::None),
+            ALOC_STACK => Ok(ArgLoc::Stack(reader.read_ne::<DE>()?.0)),
+            ALOC_REG1 => {
+                let reg = reader.read_ne::<DT>()?.0 as u32;
+                let offset = reader.read_ne::<DE>()?.0;
+                Ok(ArgLoc::Reg1(reg | offset << 16))
+            }
+            ALOC_REG2 => {
+                let reg1 = reader.read_ne::<DT>()?.0 as u32;
+                let reg2 = reader.read_ne::<DT>()?.0 as u32;
+                Ok(ArgLoc::Reg2(reg1 | reg2 << 16))
+            }
+            ALOC_RREL => {
+                let reg = reader.read_ne::<DT>()?.0;
+                let offset = reader.read_ne::<DE>()?.0;
+                Ok(ArgLoc::RRel { reg, offset })
+            }
+            ALOC_STATIC => Ok(ArgLoc::Static(reader.read_ne::<DE>()?.0)),
+            ALOC_DIST => Err(binrw::Error::AssertFail {
+                pos,
+                message: "scattered argument locations are not supported".to_string(),
+            }),
+            _ => Err(binrw::Error::AssertFail {
+                pos,
+                message: format!("unknown argument location type {:#x}", typ),
+            }),
+        }
+    }
+}
+
+/// A function argument's type, and its location under a special calling convention.
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FuncArgs(pub Types, pub Option<ArgLoc>);
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Function {
+    metadata: TypeMetadata,
+    cc: TypeMetadata,
+    pub ret: Types,
+    /// The return value's location under a special calling convention.
+    pub ret_loc: Option<ArgLoc>,
+    pub args: Vec<FuncArgs>,
+    /// The registers listed in `__spoils<...>`, one byte each as encoded.
+    pub spoiled: Vec<u8>,
+    /// `BFA_*` bits, 0x01 for noreturn, 0x02 for pure, 0x04 for high level,
+    /// 0x08 for static and 0x10 for virtual functions.
+    pub attributes: u8,
+}
+impl Function {
+    /// True for functions taking a variable argument list (`...`).
+    pub fn is_variadic(&self) -> bool {
+        self.cc.get_calling_convention().is_ellipsis()
+    }
+
+    pub fn calling_convention(&self) -> CallingConvention {
+        self.cc.get_calling_convention().convention()
+    }
+
+    /// The code and data model from the low bits of the calling convention byte.
+    pub fn memory_model(&self) -> MemoryModel {
+        MemoryModel::from_cm(self.cc.0)
+    }
+}
+
+impl BinRead for Function {
+    type Args = (TypeDepth,);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        (depth,): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        let mut spoiled = Vec::new();
+        let mut attributes = 0;
+        let mut cc = reader.read_ne::<TypeMetadata>()?;
+        while cc.get_calling_convention().is_spoiled() {
+            let nspoiled = cc.0 & !0xF0;
+            // A count of 15 stands for an attribute byte instead of registers
+            if nspoiled == 15 {
+                attributes |= reader.read_ne::<u8>()? & 0x1F;
+            } else {
+                for _ in 0..nspoiled {
+                    let reg = reader.read_ne::<u8>()?;
+                    // The size follows in its own byte instead of the high nibble
+                    if reg & 0x80 != 0 {
+                        reader.read_ne::<u8>()?;
+                    }
+                    spoiled.push(reg);
+                }
+            }
+            cc = reader.read_ne::<TypeMetadata>()?;
+        }
+        let tah = reader.read_ne::<TAH>()?;
+        let ret = reader.read_ne_args::<Types>((0, depth))?;
+        let special = cc.get_calling_convention().is_special_pe();
+        let ret_is_void =
+            matches!(&ret, Types::Unset(mdata) if mdata.get_full_type_flag().is_void());
+        let ret_loc = if special && !ret_is_void {
+            Some(reader.read_ne::<ArgLoc>()?)
+        } else {
+            None
+        };
+
+        if cc.get_calling_convention().is_void_arg() {
+            Ok(Self {
+                metadata,
+                cc,
+                ret,
+                ret_loc,
+                spoiled,
+                attributes,
+                ..Default::default()
+            })
+        } else {
+            let n = reader.read_ne::<DT>()?.0;
+            let mut args = Vec::<FuncArgs>::new();
+            for index in 0..n {
+                let pos = reader.stream_position()?;
+                let temp = match peek_u8(reader)? {
+                    Some(temp) => temp,
+                    None => {
+                        return Err(binrw::Error::AssertFail {
+                            pos,
+                            message: format!(
+                                "function declares {} arguments but the type ends after {}",
+                                n, index
+                            ),
+                        })
+                    }
+                };
+                if temp == 0xFF {
+                    reader.seek(SeekFrom::Current(1))?;
+                    let _flags = reader.read_ne::<DE>()?;
+                }
+                let typ = reader.read_ne_args::<Types>((0, depth))?;
+                let argloc = if special {
+                    Some(reader.read_ne::<ArgLoc>()?)
+                } else {
+                    None
+                };
+                args.push(FuncArgs(typ, argloc));
+            }
 
-        if cc.get_calling_convention().is_void_arg() {
             Ok(Self {
                 metadata,
-                cc,
-                ret,
+                cc,
+                ret,
+                ret_loc,
+                args,
+                spoiled,
+                attributes,
+            })
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+// #[binread]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Array {
+    pub metadata: TypeMetadata,
+    // #[br(if(metadata.get_type_flag().is_non_based()), calc(1))]
+    pub is_non_based: bool,
+    pub base: u8,
+    pub nelem: u16,
+    // #[br(if(is_non_based==1), calc(0))]
+    // non_based_base: u8,
+    // #[br(if(is_non_based==1))]
+    // non_based_nelem: DT,
+    // #[br(if(is_non_based==0))]
+    // based_info: DA, // contains base/nelem
+    pub tah: TAH,
+    pub elem_type: Types,
+}
+
+impl BinRead for Array {
+    type Args = (TypeDepth,);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        (depth,): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        let is_non_based = metadata.get_type_flag().is_non_based();
+        if is_non_based {
+            let base = 0;
+            let nelem = reader.read_ne::<DT>()?.0;
+            let tah = reader.read_ne::<TAH>()?;
+            let elem_type = reader.read_ne_args::<Types>((0, depth))?;
+            Ok(Array {
+                metadata,
+                is_non_based,
+                base,
+                nelem,
+                tah,
+                elem_type,
+            })
+        } else {
+            let da = reader.read_ne::<DA>()?;
+            let base = da.base;
+            let nelem = da.nelem as u16;
+            let tah = reader.read_ne::<TAH>()?;
+            let elem_type = reader.read_ne_args::<Types>((0, depth))?;
+            Ok(Array {
+                metadata,
+                is_non_based,
+                base,
+                nelem,
+                tah,
+                elem_type,
+            })
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+// #[binread]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Typedef {
+    pub metadata: TypeMetadata,
+    pub buf: DTBytes,
+    // #[br(if(buf.bytes[0] == '#' as u8), calc(1))]
+    pub is_ordref: bool,
+    // #[br(if(is_ordref == 1), seek_before(SeekFrom::Current(-((buf.dt.0 as i64)+(buf.dt.1 as i64)))), pad_after(buf.dt.0+buf.dt.1 as u16))]
+    pub ordinal: DE,
+    pub name: String,
+}
+
+impl BinRead for Typedef {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        let buf = reader.read_ne::<DTBytes>()?;
+        if !buf.bytes.is_empty() && buf.bytes[0] == '#' as u8 {
+            let is_ordref = true;
+            let mut cursor = binrw::io::Cursor::new(&buf.bytes[1..]);
+            let ordinal = cursor.read_ne::<DE>()?;
+            let name = String::from("");
+            Ok(Typedef {
+                metadata,
+                buf,
+                is_ordref,
+                ordinal,
+                name,
+            })
+        } else {
+            let is_ordref = false;
+            let ordinal = DE::default();
+            let name = String::from_utf8_lossy(&buf.bytes).into_owned();
+            Ok(Typedef {
+                metadata,
+                buf,
+                is_ordref,
+                ordinal,
+                name,
+            })
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+// #[binread]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Struct {
+    pub metadata: TypeMetadata,
+    // n: DT,
+    // #[br(if(n.0==0), calc(1))]
+    pub is_ref: bool,
+    // #[br(if(is_ref==1))]
+    pub ref_type: Ref,
+    // #[br(if(is_ref==1))]
+    // sdacl_attr: SDACL,
+    // #[br(if(is_ref==0), calc(n.0 & 7))]
+    // alpow: u16,
+    // #[br(if(is_ref==0 && alpow != 0), calc(1 << (alpow - 1)))]
+    pub effective_alignment: u16,
+    // #[br(if(is_ref==0))]
+    pub taudt_bits: SDACL,
+    // #[br(if(is_ref==0), count=n.0>>3)]
+    pub members: Vec<StructMember>,
+}
+
+#[derive(Clone, Debug, Default)]
+// #[binread]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Union {
+    pub metadata: TypeMetadata,
+    // n: DT,
+    // #[br(if(n.0==0), calc(1))]
+    pub is_ref: bool,
+    // #[br(if(is_ref==1))]
+    pub ref_type: Ref,
+    // #[br(if(is_ref==1))]
+    // sdacl_attr: SDACL,
+    // #[br(if(is_ref==0), calc(n.0 & 7))]
+    // alpow: u16,
+    // #[br(if(is_ref==0 && alpow != 0), calc(1 << (alpow - 1)))]
+    pub effective_alignment: u16,
+    // #[br(if(is_ref==0))]
+    pub taudt_bits: SDACL,
+    // #[br(if(is_ref==0), count=n.0>>3)]
+    pub members: Vec<UnionMember>,
+}
+
+impl BinRead for Struct {
+    type Args = (TypeDepth,);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        (depth,): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        let mut n = reader.read_ne::<DT>()?.0 as u32;
+        let mut res = Self::default();
+        res.metadata = metadata;
+        if n == 0 {
+            res.is_ref = true;
+            res.ref_type = reader.read_ne_args::<Ref>((depth,))?;
+            res.taudt_bits = reader.read_ne::<SDACL>()?;
+        } else {
+            if n == 0x7FFE {
+                n = reader.read_ne::<DE>()?.0;
+            }
+            let alpow = n & 7;
+            let mem_cnt = n >> 3;
+            if alpow == 0 {
+                res.effective_alignment = 0;
+            } else {
+                res.effective_alignment = 1 << (alpow - 1);
+            }
+            res.taudt_bits = reader.read_ne::<SDACL>()?;
+            let mut vec: Vec<StructMember> = Vec::new();
+            for _ in 0..mem_cnt {
+                vec.push(reader.read_ne_args::<StructMember>((depth,))?);
+            }
+            res.members = vec;
+        }
+        Ok(res)
+    }
+}
+
+impl BinRead for Union {
+    type Args = (TypeDepth,);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        (depth,): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        let mut n = reader.read_ne::<DT>()?.0 as u32;
+        let mut res = Self::default();
+        res.metadata = metadata;
+        if n == 0 {
+            res.is_ref = true;
+            res.ref_type = reader.read_ne_args::<Ref>((depth,))?;
+            res.taudt_bits = reader.read_ne::<SDACL>()?;
+        } else {
+            if n == 0x7FFE {
+                n = reader.read_ne::<DE>()?.0;
+            }
+            let alpow = n & 7;
+            let mem_cnt = n >> 3;
+            if alpow == 0 {
+                res.effective_alignment = 0;
+            } else {
+                res.effective_alignment = 1 << (alpow - 1);
+            }
+            res.taudt_bits = reader.read_ne::<SDACL>()?;
+            let mut vec: Vec<UnionMember> = Vec::new();
+            for _ in 0..mem_cnt {
+                vec.push(reader.read_ne_args::<UnionMember>((depth,))?);
+            }
+            res.members = vec;
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EnumMember(pub u64);
+
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Enum {
+    pub metadata: TypeMetadata,
+    pub group_sizes: Vec<DT>,
+    pub taenum_bits: TypeAttribute,
+    pub bte: u8,
+    pub members: Vec<EnumMember>,
+    pub ref_type: Ref,
+    pub is_ref: bool,
+    pub bytesize: u64,
+}
+impl Enum {
+    /// The integer type the enum is stored as, derived from its byte size.
+    /// Enums displayed as signed decimals are signed, all others unsigned.
+    pub fn base_type(&self) -> Types {
+        let base = match self.bytesize {
+            1 => 0x02,
+            2 => 0x03,
+            4 => 0x04,
+            8 => 0x05,
+            16 => 0x06,
+            _ => 0x07,
+        };
+        let sign = if self.is_signed() { 0x10 } else { 0x20 };
+        Types::Unset(TypeMetadata(base | sign))
+    }
+
+    /// 16 for hexadecimal enums, 10 for decimal ones. Character enums report
+    /// 16, see [`Enum::is_char`].
+    pub fn radix(&self) -> u32 {
+        match self.bte & 0x60 {
+            0x40 | 0x60 => 10,
+            _ => 16,
+        }
+    }
+
+    /// True for enums displayed as signed decimals (BTE_SDEC).
+    pub fn is_signed(&self) -> bool {
+        self.bte & 0x60 == 0x40
+    }
+
+    /// True for enums displayed as character constants (BTE_CHAR).
+    pub fn is_char(&self) -> bool {
+        self.bte & 0x60 == 0x20
+    }
+
+    /// A member's value as a C constant in the enum's representation.
+    fn format_member(&self, member: &EnumMember) -> String {
+        let bits = (self.bytesize * 8).clamp(8, 64) as u32;
+        match self.bte & 0x60 {
+            0x20 if (0x20..0x7F).contains(&member.0) => match member.0 as u8 {
+                c @ (b'\'' | b'\\') => format!("'\\{}'", c as char),
+                c => format!("'{}'", c as char),
+            },
+            // Sign extend from the enum's size
+            0x40 => ((member.0 << (64 - bits)) as i64 >> (64 - bits)).to_string(),
+            0x60 => member.0.to_string(),
+            _ => format!("{:#x}", member.0),
+        }
+    }
+
+    /// The groups of a bitmask enum (BTE_BITFIELD) with their masks. The
+    /// first member of each group is its mask, a single member group being
+    /// a flag that is its own mask. Empty for other enums.
+    pub fn groups(&self) -> Vec<(u64, Vec<EnumMember>)> {
+        let mut members = self.members.iter();
+        let mut groups = Vec::with_capacity(self.group_sizes.len());
+        for size in &self.group_sizes {
+            let group: Vec<EnumMember> = members.by_ref().take(size.0 as usize).cloned().collect();
+            if let Some(mask) = group.first() {
+                groups.push((mask.0, group));
+            }
+        }
+        groups
+    }
+}
+
+impl BinRead for Enum {
+    type Args = (u8, TypeDepth);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        args: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        let mut n = reader.read_ne::<DT>()?.0 as u32;
+        let mut is_ref = false;
+        if n == 0 {
+            let ref_type = reader.read_ne_args::<Ref>((args.1,))?;
+            let taenum_bits = reader.read_ne::<SDACL>()?.0;
+            is_ref = true;
+            return Ok(Enum {
+                metadata,
+                ref_type,
+                taenum_bits,
+                is_ref,
                 ..Default::default()
-            })
+            });
+        } else {
+            if n == 0x7FFE {
+                n = reader.read_ne::<DE>()?.0;
+            }
+            let taenum_bits = reader.read_ne::<TAH>()?.0;
+            let bte = reader.read_ne::<u8>()?;
+            let mut cur: u64 = 0;
+            let mut hi = DE::default();
+            let mut bytesize = 0;
+            let mask: u64 = {
+                let emsize = bte & 0x07;
+                let mut bitsize = 0_u64;
+                if emsize != 0 {
+                    bytesize = 1 << (emsize - 1);
+                } else if args.0 != 0 {
+                    bytesize = args.0 as u64;
+                } else {
+                    bytesize = 4;
+                }
+                bitsize = bytesize * 8;
+                if bitsize < 64 {
+                    (1 << bitsize) - 1
+                } else {
+                    0xFFFFFFFFFFFFFFFF
+                }
+            };
+            let mut group_sizes = Vec::<DT>::new();
+            let mut group_end = 0;
+            let mut members = Vec::<EnumMember>::new();
+            for index in 0..n {
+                // Bitmask enums store each group's size before its first member
+                if (bte & 0x10) > 0 && index == group_end {
+                    let size = reader.read_ne::<DT>()?;
+                    group_end += size.0 as u32;
+                    group_sizes.push(size);
+                }
+                let lo = reader.read_ne::<DE>()?;
+                if (taenum_bits.0 & 0x0020) > 0 {
+                    hi = reader.read_ne::<DE>()?;
+                }
+                // Each member is a delta from the previous one, the high word
+                // only being stored for 64 bit enums (TAENUM_64BIT)
+                cur = cur.wrapping_add(lo.0 as u64 | (hi.0 as u64) << 32) & mask;
+                members.push(EnumMember(cur));
+            }
+            return Ok(Enum {
+                metadata,
+                group_sizes,
+                taenum_bits,
+                bte,
+                members,
+                is_ref,
+                bytesize,
+                ..Default::default()
+            });
+        }
+        Ok(Default::default())
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Bitfield {
+    metadata: TypeMetadata,
+    pub unsigned: bool,
+    pub width: u16,
+    pub nbytes: i32,
+}
+
+impl BinRead for Bitfield {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let metadata = reader.read_ne::<TypeMetadata>()?;
+        // BTMT_BFLDI8, BTMT_BFLDI16, BTMT_BFLDI32 and BTMT_BFLDI64
+        let nbytes = 1 << (metadata.get_type_flag().0 >> 4);
+        // The DT packs the width above a sign bit
+        let dt = reader.read_ne::<DT>()?;
+        let width = dt.0 >> 1;
+        let unsigned = (dt.0 & 1) > 0;
+        let _tah = reader.read_ne::<TAH>()?;
+        Ok(Self {
+            metadata,
+            unsigned,
+            width,
+            nbytes,
+        })
+    }
+}
+
+impl BinRead for Types {
+    /// The section's `size_e` and the nesting left.
+    type Args = (u8, TypeDepth);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        (size_e, depth): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let depth = depth.nest(reader.stream_position()?)?;
+        let metadata = TypeMetadata(reader.read_ne()?);
+        if metadata.get_base_type_flag().is_typeid_last() {
+            // reader.seek(SeekFrom::Current(1));
+            Ok(Types::Unset(metadata))
+        } else if metadata.get_base_type_flag().is_reserved() {
+            Ok(Types::Reserved(metadata))
+        } else {
+            reader.seek(SeekFrom::Current(-1));
+            if metadata.get_base_type_flag().is_pointer() {
+                Ok(Types::Pointer(Box::new(reader.read_ne_args((depth,))?)))
+            } else if metadata.get_base_type_flag().is_function() {
+                Ok(Types::Function(Box::new(reader.read_ne_args((depth,))?)))
+            } else if metadata.get_base_type_flag().is_array() {
+                Ok(Types::Array(Box::new(reader.read_ne_args((depth,))?)))
+            } else if metadata.get_full_type_flag().is_typedef() {
+                Ok(Types::Typedef(reader.read_ne()?))
+            } else if metadata.get_full_type_flag().is_union() {
+                Ok(Types::Union(Box::new(reader.read_ne_args((depth,))?)))
+            } else if metadata.get_full_type_flag().is_struct() {
+                Ok(Types::Struct(Box::new(reader.read_ne_args((depth,))?)))
+            } else if metadata.get_full_type_flag().is_enum() {
+                Ok(Types::Enum(Box::new(reader.read_ne_args((size_e, depth))?)))
+            } else if metadata.get_base_type_flag().is_bitfield() {
+                Ok(Types::Bitfield(reader.read_ne()?))
+            } else {
+                trace_event!(debug, metadata = metadata.0, "unclassified type byte");
+                Ok(Types::Unknown(read_null_terminated(reader)?))
+            }
+        }
+    }
+}
+
+/// Reads bytes up to the next null terminator, which is consumed but not returned.
+/// Hitting the end of the stream also terminates the string instead of failing.
+fn read_null_terminated<R: Read>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut byte = [0_u8; 1];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == 0 => break,
+            Ok(_) => bytes.push(byte[0]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Reads `len` bytes, growing the buffer as they arrive so that a corrupt
+/// length ends in an end of input error rather than a huge allocation.
+fn read_bytes<R: Read + Seek>(
+    reader: &mut R,
+    _: &ReadOptions,
+    (len,): (u64,),
+) -> BinResult<Vec<u8>> {
+    let pos = reader.stream_position()?;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(binrw::Error::AssertFail {
+            pos,
+            message: format!("expected {:#x} bytes, only {:#x} remain", len, bytes.len()),
+        });
+    }
+    Ok(bytes)
+}
+
+/// Where a type failed to parse, with the bytes around that offset.
+#[derive(Debug, Clone)]
+pub struct TypeParseError {
+    /// The stream offset the underlying error was raised at. Inside a
+    /// zipped bucket, the offset into its inflated data.
+    pub offset: u64,
+    /// The offset of the first byte in `bytes`.
+    pub bytes_offset: u64,
+    /// Up to 8 bytes on either side of `offset`.
+    pub bytes: Vec<u8>,
+    /// The underlying error message.
+    pub message: String,
+}
+
+impl Display for TypeParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "type parse failed at {:#x} ({}), bytes from {:#x}: {:02x?}",
+            self.offset, self.message, self.bytes_offset, self.bytes
+        )
+    }
+}
+
+impl std::error::Error for TypeParseError {}
+
+fn error_pos(err: &binrw::Error) -> Option<u64> {
+    match err.root_cause() {
+        binrw::Error::BadMagic { pos, .. }
+        | binrw::Error::AssertFail { pos, .. }
+        | binrw::Error::Custom { pos, .. }
+        | binrw::Error::NoVariantMatch { pos }
+        | binrw::Error::EnumErrors { pos, .. } => Some(*pos),
+        _ => None,
+    }
+}
+
+fn read_type_with_context<R: Read + Seek>(
+    reader: &mut R,
+    options: &ReadOptions,
+    args: (u8, TypeDepth),
+) -> BinResult<Types> {
+    let start = reader.stream_position()?;
+    let err = match Types::read_options(reader, options, args) {
+        Ok(typ) => return Ok(typ),
+        // Running out of data isn't a malformed type, leave that to the caller
+        Err(err) if err.is_eof() => return Err(err),
+        Err(err) => err,
+    };
+
+    let offset = match error_pos(&err) {
+        Some(pos) => pos,
+        None => reader.stream_position()?,
+    };
+    let bytes_offset = offset.saturating_sub(8).max(start);
+    reader.seek(SeekFrom::Start(bytes_offset))?;
+    let mut bytes = Vec::new();
+    reader
+        .take(offset - bytes_offset + 8)
+        .read_to_end(&mut bytes)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    trace_event!(warn, offset, "type parse failed");
+    Err(binrw::Error::Custom {
+        pos: start,
+        err: Box::new(TypeParseError {
+            offset,
+            bytes_offset,
+            bytes,
+            message: err.to_string(),
+        }),
+    })
+}
+
+impl BinRead for NullVecLenString {
+    type Args = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _: &binrw::ReadOptions,
+        _: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let vec = read_null_terminated(reader)?;
+        let nvec = split_len_strings(&vec)
+            .map(|string| String::from_utf8_lossy(string).into_owned())
+            .collect();
+        Ok(NullVecLenString(nvec))
+    }
+}
+
+/// Splits a list of length prefixed strings, as used for field names and
+/// comments. The length byte counts itself, a truncated entry keeps what is there.
+fn split_len_strings(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let len = *bytes.get(pos)? as usize;
+        let string = &bytes[(pos + 1).min(bytes.len())..(pos + len).min(bytes.len())];
+        pos += len.max(1);
+        Some(string)
+    })
+}
+
+#[derive(BinRead, Debug, Clone)]
+#[br(import(size_e: u8, depth: TypeDepth))]
+pub struct TILTypeInfo {
+    flags: u32,
+    pub name: binrw::NullString,
+    #[br(args { is_u64: (flags >> 31u32) != 0})]
+    pub ordinal: TILOrdinal,
+    #[br(args(size_e, depth), restore_position, parse_with = read_type_with_context)]
+    pub tinfo: Types,
+    _info: binrw::NullString,
+    cmt: binrw::NullString,
+    pub fields: NullVecLenString,
+    fieldcmts: binrw::NullString,
+    sclass: u8,
+}
+
+impl NullVecLenString {
+    fn to_bytes(&self) -> BinResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for string in &self.0 {
+            bytes.push(length_byte(string.len() + 1, bytes.len())?);
+            bytes.extend_from_slice(string.as_bytes());
+        }
+        bytes.push(0);
+        Ok(bytes)
+    }
+}
+
+/// `len` as the byte in front of a length prefixed string, an error at
+/// `pos` when it doesn't fit.
+fn length_byte(len: usize, pos: usize) -> BinResult<u8> {
+    u8::try_from(len).map_err(|_| binrw::Error::AssertFail {
+        pos: pos as u64,
+        message: format!("length {} doesn't fit in its length byte", len),
+    })
+}
+
+impl TILTypeInfo {
+    /// The entry's name, borrowed when it is valid UTF-8 and lossily converted otherwise.
+    pub fn name_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name.0)
+    }
+
+    /// The comment of the type itself, empty when it has none.
+    pub fn comment(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.cmt.0)
+    }
+
+    /// The comment of the member at `index`, in the same order as `fields`.
+    /// Entries starting with a control byte hold member annotations rather
+    /// than comments and are skipped.
+    pub fn field_comment(&self, index: usize) -> Option<Cow<'_, str>> {
+        split_len_strings(&self.fieldcmts.0)
+            .nth(index)
+            .filter(|comment| comment.first().is_some_and(|&b| b >= 0x20))
+            .map(String::from_utf8_lossy)
+    }
+
+    fn to_bytes(&self) -> BinResult<Vec<u8>> {
+        let mut bytes = self.flags.to_le_bytes().to_vec();
+        write_null_string(&mut bytes, &self.name);
+        match self.ordinal {
+            TILOrdinal::U32(ordinal) => bytes.extend_from_slice(&ordinal.to_le_bytes()),
+            TILOrdinal::U64(ordinal) => bytes.extend_from_slice(&ordinal.to_le_bytes()),
+        }
+        write_null_string(&mut bytes, &self._info);
+        write_null_string(&mut bytes, &self.cmt);
+        bytes.extend(self.fields.to_bytes()?);
+        write_null_string(&mut bytes, &self.fieldcmts);
+        bytes.push(self.sclass);
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TILTypeInfo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("TILTypeInfo", 5)?;
+        state.serialize_field("name", &self.name_str())?;
+        state.serialize_field("ordinal", &self.ordinal.value())?;
+        state.serialize_field("tinfo", &self.tinfo)?;
+        state.serialize_field("fields", &self.fields.0)?;
+        state.serialize_field("cmt", &String::from_utf8_lossy(&self.cmt.0))?;
+        state.end()
+    }
+}
+
+fn write_null_string(bytes: &mut Vec<u8>, string: &binrw::NullString) {
+    bytes.extend_from_slice(&string.0);
+    bytes.push(0);
+}
+
+fn write_type_info(bytes: &mut Vec<u8>, type_info: &[TILTypeInfo]) -> BinResult<()> {
+    let mut data = Vec::new();
+    for info in type_info {
+        data.extend(info.to_bytes()?);
+    }
+    bytes.extend_from_slice(&(type_info.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend(data);
+    Ok(())
+}
+
+#[derive(Debug)]
+#[binread]
+#[br(import { size_e: u8, depth: TypeDepth })]
+pub struct TILBucket {
+    pub ndefs: u32,
+    len: u32,
+    #[br(args(len as u64), parse_with = read_bytes)]
+    data: Vec<u8>,
+    #[br(parse_with = read_bucket_type_infos, args(&data, ndefs, size_e, depth))]
+    pub type_info: Vec<TILTypeInfo>,
+    #[br(default)]
+    stats: Option<BucketStats>,
+}
+
+#[derive(Debug)]
+pub struct TILBucketZip {
+    pub ndefs: u32,
+    len: u32,
+    // #[br(args{ count: ndefs.try_into().unwrap(), inner: (size_e,) },restore_position)]
+    pub type_info: Vec<TILTypeInfo>,
+    // #[br(count = compressed_len)]
+    data: Vec<u8>,
+    compressed_data: Option<Vec<u8>>,
+    stats: Option<BucketStats>,
+}
+
+impl TILBucket {
+    /// Length of the bucket's type data in bytes.
+    pub fn data_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the declared and the actually parsed number of entries when
+    /// the bucket's data ended before `ndefs` entries could be read.
+    pub fn ndefs_mismatch(&self) -> Option<(u32, usize)> {
+        if self.type_info.len() == self.ndefs as usize {
+            None
         } else {
-            let n = reader.read_ne::<DT>()?.0;
-            let mut args = Vec::<FuncArgs>::new();
-            for ind in 0..n {
-                let temp = reader.read_ne::<u8>()?;
-                reader.seek(SeekFrom::Current(-1));
-                if temp == 0xFF {
-                    reader.seek(SeekFrom::Current(1));
-                    let flags = reader.read_ne::<DE>()?;
-                }
-                let fnarg = FuncArgs(reader.read_ne::<Types>()?);
-                if cc.get_calling_convention().is_special_pe() {
-                    panic!("Argloc unhandled");
+            Some((self.ndefs, self.type_info.len()))
+        }
+    }
+}
+
+/// Parses up to `ndefs` entries out of a bucket's data. Entries never read
+/// past the end of `data`, and the entries parsed so far are kept if it runs
+/// out, the shortfall being reported through `ndefs_mismatch()`. Malformed
+/// entries are errors, positioned `base` bytes past the start of `data`.
+fn read_type_infos(
+    data: &[u8],
+    base: u64,
+    ndefs: u32,
+    size_e: u8,
+    depth: TypeDepth,
+) -> BinResult<Vec<TILTypeInfo>> {
+    let mut cursor = BaseCursor {
+        cursor: binrw::io::Cursor::new(data),
+        base,
+    };
+    let mut type_info = Vec::new();
+    for _ in 0..ndefs {
+        match cursor.read_ne_args::<TILTypeInfo>((size_e, depth)) {
+            Ok(info) => type_info.push(info),
+            Err(err) if err.is_eof() => {
+                trace_event!(
+                    warn,
+                    ndefs,
+                    parsed = type_info.len(),
+                    "bucket data ended early"
+                );
+                break;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(type_info)
+}
+
+/// Reads the entries of an uncompressed bucket, whose `data` was just read,
+/// so that errors carry their position in the file.
+fn read_bucket_type_infos<R: Read + Seek>(
+    reader: &mut R,
+    _: &ReadOptions,
+    (data, ndefs, size_e, depth): (&[u8], u32, u8, TypeDepth),
+) -> BinResult<Vec<TILTypeInfo>> {
+    let base = reader.stream_position()? - data.len() as u64;
+    read_type_infos(data, base, ndefs, size_e, depth)
+}
+
+/// A cursor over bucket data reporting positions as if the data started
+/// `base` bytes into the stream, so that errors point into the file.
+struct BaseCursor<'a> {
+    cursor: binrw::io::Cursor<&'a [u8]>,
+    base: u64,
+}
+
+impl Read for BaseCursor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.cursor.read(buf)
+    }
+}
+
+impl Seek for BaseCursor<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => {
+                SeekFrom::Start(pos.checked_sub(self.base).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "seek before the bucket data",
+                    )
+                })?)
+            }
+            pos => pos,
+        };
+        Ok(self.cursor.seek(pos)? + self.base)
+    }
+}
+
+impl TILBucketZip {
+    /// Length of the bucket's type data in bytes, after decompression.
+    pub fn data_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The zlib stream exactly as it was stored in the file. Only kept when
+    /// asked for, see [`TILSection::parse_with_compressed_data`] and
+    /// [`IdbParser::keep_compressed_data`].
+    pub fn compressed_data(&self) -> Option<&[u8]> {
+        self.compressed_data.as_deref()
+    }
+
+    /// Re-emits the bucket. With the original compressed data kept it is
+    /// reproduced byte for byte, otherwise the data is compressed again into
+    /// an equivalent stream.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let compressed = match &self.compressed_data {
+            Some(compressed) => Cow::Borrowed(compressed.as_slice()),
+            None => Cow::Owned(miniz_oxide::deflate::compress_to_vec_zlib(&self.data, 6)),
+        };
+        let mut bytes = self.ndefs.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&compressed);
+        bytes
+    }
+
+    /// Returns the declared and the actually parsed number of entries when
+    /// the decompressed data ended before `ndefs` entries could be read.
+    pub fn ndefs_mismatch(&self) -> Option<(u32, usize)> {
+        if self.type_info.len() == self.ndefs as usize {
+            None
+        } else {
+            Some((self.ndefs, self.type_info.len()))
+        }
+    }
+
+    /// Converts to an uncompressed bucket. The data was already inflated and
+    /// its entries parsed while reading, so this only copies them over.
+    pub fn unzip(&self) -> TILBucket {
+        TILBucket {
+            ndefs: self.ndefs,
+            len: self.len,
+            type_info: self.type_info.clone(),
+            data: self.data.clone(),
+            stats: self.stats,
+        }
+    }
+}
+
+/// A zipped TIL bucket failed to inflate. Carried in `binrw::Error::Custom`,
+/// whose position is the start of the zlib stream.
+pub enum DecompressionError {
+    /// The inflate status and how many compressed bytes were consumed.
+    Error(TINFLStatus, usize),
+}
+
+impl DecompressionError {
+    pub fn status(&self) -> TINFLStatus {
+        match self {
+            DecompressionError::Error(status, _) => *status,
+        }
+    }
+}
+
+impl Debug for DecompressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Display for DecompressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let DecompressionError::Error(status, consumed) = self;
+        let message = match status {
+            TINFLStatus::FailedCannotMakeProgress => "unexpected end of input",
+            TINFLStatus::BadParam => "invalid output buffer",
+            TINFLStatus::Adler32Mismatch => "adler32 checksum mismatch",
+            TINFLStatus::Failed => "invalid zlib header or deflate data",
+            TINFLStatus::Done => "done",
+            TINFLStatus::NeedsMoreInput => "needs more input",
+            TINFLStatus::HasMoreOutput => "output buffer full",
+        };
+        write!(
+            f,
+            "Decompression Error: {} after {:#x} compressed bytes",
+            message, consumed
+        )
+    }
+}
+
+impl std::error::Error for DecompressionError {}
+
+/// How much of a zipped bucket is buffered up front, whatever its header
+/// declares. Buffers grow from there as data actually arrives, so a bucket
+/// claiming gigabytes in a few bytes of file cannot reserve them.
+const INFLATE_CHUNK: usize = 0x10000;
+
+/// Inflates the `compressed_len` byte zlib stream at the reader's position a
+/// chunk at a time, starting from a buffer of at most [`INFLATE_CHUNK`] bytes
+/// of the `len` the bucket declares. With `keep_compressed` the stored
+/// stream is returned as well.
+fn inflate_zlib<R: Read + Seek>(
+    reader: &mut R,
+    compressed_len: usize,
+    len: u32,
+    keep_compressed: bool,
+) -> BinResult<(Vec<u8>, Option<Vec<u8>>)> {
+    use miniz_oxide::inflate::core::{decompress, inflate_flags, DecompressorOxide};
+
+    let pos = reader.stream_position()?;
+    let mut decompressor = Box::<DecompressorOxide>::default();
+    let mut out = vec![0; (len as usize).clamp(1, INFLATE_CHUNK)];
+    let mut out_pos = 0;
+    let mut chunk = Vec::new();
+    let mut in_pos = 0;
+    let mut read = 0;
+    let mut kept = keep_compressed.then(Vec::new);
+    loop {
+        if in_pos == chunk.len() && read < compressed_len {
+            chunk.resize((compressed_len - read).min(INFLATE_CHUNK), 0);
+            reader.read_exact(&mut chunk)?;
+            if let Some(kept) = &mut kept {
+                kept.extend_from_slice(&chunk);
+            }
+            read += chunk.len();
+            in_pos = 0;
+        }
+        let mut flags = inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER
+            | inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+        if read < compressed_len {
+            flags |= inflate_flags::TINFL_FLAG_HAS_MORE_INPUT;
+        }
+        let (status, consumed, written) = decompress(
+            &mut decompressor,
+            &chunk[in_pos..],
+            &mut out,
+            out_pos,
+            flags,
+        );
+        in_pos += consumed;
+        out_pos += written;
+        match status {
+            TINFLStatus::Done => {
+                out.truncate(out_pos);
+                // Keep the whole stored stream, even past the end of the zlib data
+                if let Some(kept) = &mut kept {
+                    let rest = (compressed_len - read) as u64;
+                    if reader.take(rest).read_to_end(kept)? as u64 != rest {
+                        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                    }
                 }
-                args.push(fnarg);
+                return Ok((out, kept));
+            }
+            TINFLStatus::HasMoreOutput => out.resize(out.len() * 2, 0),
+            TINFLStatus::NeedsMoreInput => {}
+            err => {
+                return Err(binrw::Error::Custom {
+                    pos,
+                    err: Box::new(DecompressionError::Error(err, read - chunk.len() + in_pos)),
+                })
+            }
+        }
+    }
+}
+
+fn stream_len<R: Read + Seek>(reader: &mut R) -> std::io::Result<u64> {
+    let old_pos = reader.stream_position()?;
+    let len = reader.seek(SeekFrom::End(0))?;
+
+    // Avoid seeking a third time when we were already at the end of the
+    // stream. The branch is usually way cheaper than a seek operation.
+    if old_pos != len {
+        reader.seek(SeekFrom::Start(old_pos))?;
+    }
+
+    Ok(len)
+}
+
+impl BinRead for TILBucketZip {
+    /// `size_e`, whether to collect stats, the type nesting allowed and
+    /// whether to keep the compressed data.
+    type Args = (u8, bool, TypeDepth, bool);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        options: &ReadOptions,
+        args: Self::Args,
+    ) -> BinResult<Self> {
+        let ndefs = reader.read_ne()?;
+        let len = reader.read_ne()?;
+        let compressed_len = reader.read_ne::<u32>()?;
+
+        let restore = reader.stream_position()?;
+
+        let decompress_start = Instant::now();
+        // An empty bucket may have no zlib stream at all.
+        let (data, compressed_data) = if compressed_len == 0 {
+            (Vec::new(), args.3.then(Vec::new))
+        } else {
+            inflate_zlib(reader, compressed_len as usize, len, args.3)?
+        };
+        let decompress_time = decompress_start.elapsed();
+
+        let parse_start = Instant::now();
+        trace_event!(
+            debug,
+            compressed_len,
+            len = data.len(),
+            "decompressed bucket"
+        );
+
+        let post = restore + compressed_len as u64;
+        let type_info = read_type_infos(&data, 0, ndefs, args.0, args.2)?;
+
+        reader.seek(SeekFrom::Start(post))?;
+
+        let stats = args.1.then(|| BucketStats {
+            entries: type_info.len(),
+            decompress_time,
+            parse_time: parse_start.elapsed(),
+        });
+        Ok(Self {
+            ndefs,
+            len,
+            type_info,
+            data,
+            compressed_data,
+            stats,
+        })
+    }
+}
+
+#[derive(Debug)]
+// #[binread]
+// #[br(import { is_zip: bool })]
+pub enum TILBucketType {
+    // #[br(pre_assert(is_zip == false))]
+    Default(TILBucket),
+    // #[br(pre_assert(is_zip == true))]
+    Zip(TILBucketZip),
+}
+
+impl BinRead for TILBucketType {
+    /// Whether the bucket is zipped, then the [`TILBucketZip`] args.
+    type Args = (bool, u8, bool, TypeDepth, bool);
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        options: &ReadOptions,
+        args: Self::Args,
+    ) -> BinResult<Self> {
+        trace_span!("til_bucket", zip = args.0);
+        if args.0 == false {
+            let parse_start = Instant::now();
+            let mut bucket: TILBucket = reader.read_ne_args(TILBucketBinReadArgs {
+                size_e: args.1,
+                depth: args.3,
+            })?;
+            if args.2 {
+                bucket.stats = Some(BucketStats {
+                    entries: bucket.type_info.len(),
+                    decompress_time: Duration::ZERO,
+                    parse_time: parse_start.elapsed(),
+                });
             }
+            trace_event!(debug, entries = bucket.type_info.len(), "parsed bucket");
+            Ok(Self::Default(bucket))
+        } else {
+            let bucket: TILBucketZip = reader.read_ne_args((args.1, args.2, args.3, args.4))?;
+            trace_event!(debug, entries = bucket.type_info.len(), "parsed bucket");
+            Ok(Self::Zip(bucket))
+        }
+    }
+}
+
+impl TILBucketType {
+    fn type_info(&self) -> &[TILTypeInfo] {
+        match self {
+            TILBucketType::Default(def) => &def.type_info,
+            TILBucketType::Zip(zip) => &zip.type_info,
+        }
+    }
+
+    fn stats(&self) -> BucketStats {
+        match self {
+            TILBucketType::Default(def) => def.stats,
+            TILBucketType::Zip(zip) => zip.stats,
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// Timing and size information for a single parsed bucket.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BucketStats {
+    pub entries: usize,
+    /// Always zero for uncompressed buckets.
+    pub decompress_time: Duration,
+    pub parse_time: Duration,
+}
+
+/// Per-bucket statistics collected by [`TILSection::parse_with_stats`].
+/// The macros bucket isn't parsed, so it has no entry here.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParseStats {
+    pub symbols: BucketStats,
+    pub types: BucketStats,
+}
+
+#[binread]
+#[br(import(
+    is_standalone: bool,
+    collect_stats: bool,
+    depth: TypeDepth,
+    keep_compressed: bool
+))]
+pub struct TILSection {
+    #[br(if(is_standalone == false))]
+    header: IDBSectionHeader,
+    #[br(
+    count = 6,
+    map = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned(),
+    assert(signature == "IDATIL"))]
+    signature: String,
+    format: u32,
+    flags: u32,
+    #[br(temp)]
+    title_len: u8,
+    #[br(
+    count = title_len,
+    map = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned())]
+    title: String,
+    #[br(temp)]
+    base_len: u8,
+    #[br(
+    count = base_len,
+    map = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned())]
+    base: String,
+    id: u8,
+    cm: u8,
+    size_i: u8,
+    size_b: u8,
+    size_e: u8,
+    def_align: u8,
+    #[br(if((flags & TIL_ESI) > 0))]
+    size_s: Option<u8>,
+    #[br(if((flags & TIL_ESI) > 0))]
+    size_l: Option<u8>,
+    #[br(if((flags & TIL_ESI) > 0))]
+    size_ll: Option<u8>,
+    #[br(if((flags & TIL_SLD) > 0))]
+    size_ldbl: Option<u8>,
+    #[br(args((flags & TIL_ZIP) > 0, size_e, collect_stats, depth, keep_compressed))]
+    pub symbols: TILBucketType,
+    #[br(if((flags & TIL_ORD) > 0))]
+    type_ordinal_numbers: Option<u32>,
+    #[br(args((flags & TIL_ZIP) > 0, size_e, collect_stats, depth, keep_compressed))]
+    pub types: TILBucketType,
+    // TODO: Fix this, I think the structures differ from the other buckets.
+    // #[br(args((flags & TIL_ZIP) > 0, size_e))]
+    // macros: TILBucketType,
+    #[br(calc = OnceLock::new())]
+    index: OnceLock<TypeIndex>,
+}
 
-            Ok(Self {
-                metadata,
-                cc,
-                ret,
-                args,
-            })
+// Written out to leave the lookup index out, which depends on whether a
+// lookup happened yet rather than on the parsed section
+impl Debug for TILSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TILSection")
+            .field("header", &self.header)
+            .field("signature", &self.signature)
+            .field("format", &self.format)
+            .field("flags", &self.flags)
+            .field("title", &self.title)
+            .field("base", &self.base)
+            .field("id", &self.id)
+            .field("cm", &self.cm)
+            .field("size_i", &self.size_i)
+            .field("size_b", &self.size_b)
+            .field("size_e", &self.size_e)
+            .field("def_align", &self.def_align)
+            .field("size_s", &self.size_s)
+            .field("size_l", &self.size_l)
+            .field("size_ll", &self.size_ll)
+            .field("size_ldbl", &self.size_ldbl)
+            .field("symbols", &self.symbols)
+            .field("type_ordinal_numbers", &self.type_ordinal_numbers)
+            .field("types", &self.types)
+            .finish()
+    }
+}
+
+/// Positions of the types bucket entries by name and ordinal, built on the
+/// first lookup. The first entry wins when several share a key.
+#[derive(Debug, Default)]
+struct TypeIndex {
+    by_name: HashMap<Vec<u8>, usize>,
+    by_ordinal: HashMap<u64, usize>,
+}
+
+impl TypeIndex {
+    fn new(type_info: &[TILTypeInfo]) -> Self {
+        let mut index = Self::default();
+        for (position, info) in type_info.iter().enumerate() {
+            index.by_name.entry(info.name.0.clone()).or_insert(position);
+            index
+                .by_ordinal
+                .entry(info.ordinal.value())
+                .or_insert(position);
         }
+        index
     }
 }
 
-#[derive(Clone, Debug)]
-// #[binread]
-pub struct Array {
-    pub metadata: TypeMetadata,
-    // #[br(if(metadata.get_type_flag().is_non_based()), calc(1))]
-    pub is_non_based: bool,
-    pub base: u8,
-    pub nelem: u16,
-    // #[br(if(is_non_based==1), calc(0))]
-    // non_based_base: u8,
-    // #[br(if(is_non_based==1))]
-    // non_based_nelem: DT,
-    // #[br(if(is_non_based==0))]
-    // based_info: DA, // contains base/nelem
-    pub tah: TAH,
-    pub elem_type: Types,
+/// The ID2 section. Its layout isn't documented. Some databases store
+/// further netnode records there as a "B-tree v2" laid out like ID0, and
+/// those are decoded. Any other body is kept as stored.
+#[derive(Debug)]
+pub enum ID2Section {
+    /// The section's b-tree, read like [`ID0Section`].
+    BTree(ID0Section),
+    /// The section body after its header, inflated if it was compressed.
+    Raw(Vec<u8>),
 }
 
-impl BinRead for Array {
+impl BinRead for ID2Section {
     type Args = ();
 
     fn read_options<R: Read + Seek>(
         reader: &mut R,
-        _: &binrw::ReadOptions,
+        _: &ReadOptions,
         _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let is_non_based = metadata.get_type_flag().is_non_based();
-        if is_non_based {
-            let base = 0;
-            let nelem = reader.read_ne::<DT>()?.0;
-            let tah = reader.read_ne::<TAH>()?;
-            let elem_type = reader.read_ne::<Types>()?;
-            Ok(Array {
-                metadata,
-                is_non_based,
-                base,
-                nelem,
-                tah,
-                elem_type,
-            })
-        } else {
-            let da = reader.read_ne::<DA>()?;
-            let base = da.base;
-            let nelem = da.nelem as u16;
-            let tah = reader.read_ne::<TAH>()?;
-            let elem_type = reader.read_ne::<Types>()?;
-            Ok(Array {
-                metadata,
-                is_non_based,
-                base,
-                nelem,
-                tah,
-                elem_type,
-            })
+    ) -> BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let (header, data) = read_section_body(reader)?;
+        match ID0Section::from_body(header, &data, pos) {
+            Ok(btree) => Ok(Self::BTree(btree)),
+            Err(_err) => {
+                trace_event!(debug, error = %_err, "ID2 isn't a b-tree");
+                Ok(Self::Raw(data))
+            }
         }
     }
 }
 
-#[derive(Clone, Default, Debug)]
-// #[binread]
-pub struct Typedef {
-    pub metadata: TypeMetadata,
-    pub buf: DTBytes,
-    // #[br(if(buf.bytes[0] == '#' as u8), calc(1))]
-    pub is_ordref: bool,
-    // #[br(if(is_ordref == 1), seek_before(SeekFrom::Current(-((buf.dt.0 as i64)+(buf.dt.1 as i64)))), pad_after(buf.dt.0+buf.dt.1 as u16))]
-    pub ordinal: DE,
-    pub name: String,
-}
+impl ID2Section {
+    /// True when the section has a body at all.
+    pub fn is_present(&self) -> bool {
+        match self {
+            Self::BTree(_) => true,
+            Self::Raw(data) => !data.is_empty(),
+        }
+    }
 
-impl BinRead for Typedef {
-    type Args = ();
+    /// The section's b-tree, if it is one.
+    pub fn btree(&self) -> Option<&ID0Section> {
+        match self {
+            Self::BTree(btree) => Some(btree),
+            Self::Raw(_) => None,
+        }
+    }
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let buf = reader.read_ne::<DTBytes>()?;
-        if !buf.bytes.is_empty() && buf.bytes[0] == '#' as u8 {
-            let is_ordref = true;
-            let mut cursor = binrw::io::Cursor::new(&buf.bytes[1..]);
-            let ordinal = cursor.read_ne::<DE>()?;
-            let name = String::from("");
-            Ok(Typedef {
-                metadata,
-                buf,
-                is_ordref,
-                ordinal,
-                name,
-            })
-        } else {
-            let is_ordref = false;
-            let ordinal = DE::default();
-            let name = String::from_utf8_lossy(&buf.bytes).into_owned();
-            Ok(Typedef {
-                metadata,
-                buf,
-                is_ordref,
-                ordinal,
-                name,
-            })
+    /// The body of a section that isn't a b-tree.
+    pub fn raw(&self) -> Option<&[u8]> {
+        match self {
+            Self::BTree(_) => None,
+            Self::Raw(data) => Some(data),
         }
     }
+
+    /// Every record of the b-tree in ascending key order, none when the
+    /// section isn't one.
+    pub fn entries(&self) -> impl Iterator<Item = &KeyValueEntry> {
+        self.btree().into_iter().flat_map(|btree| btree.entries())
+    }
 }
 
-#[derive(Clone, Debug, Default)]
-// #[binread]
-pub struct Struct {
-    pub metadata: TypeMetadata,
-    // n: DT,
-    // #[br(if(n.0==0), calc(1))]
-    pub is_ref: bool,
-    // #[br(if(is_ref==1))]
-    pub ref_type: Ref,
-    // #[br(if(is_ref==1))]
-    // sdacl_attr: SDACL,
-    // #[br(if(is_ref==0), calc(n.0 & 7))]
-    // alpow: u16,
-    // #[br(if(is_ref==0 && alpow != 0), calc(1 << (alpow - 1)))]
-    pub effective_alignment: u16,
-    // #[br(if(is_ref==0))]
-    pub taudt_bits: SDACL,
-    // #[br(if(is_ref==0), count=n.0>>3)]
-    pub members: Vec<StructMember>,
+/// Configures how an [`IDB`] is parsed.
+///
+/// `IdbParser::new().parse(bytes)` is equivalent to [`IDB::parse`].
+/// Skipped sections are left as `None`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdbParser {
+    skip_id0: bool,
+    skip_id1: bool,
+    skip_nam: bool,
+    skip_seg: bool,
+    skip_til: bool,
+    skip_id2: bool,
+    depth: TypeDepth,
+    keep_compressed: bool,
 }
 
-#[derive(Clone, Debug, Default)]
-// #[binread]
-pub struct Union {
-    pub metadata: TypeMetadata,
-    // n: DT,
-    // #[br(if(n.0==0), calc(1))]
-    pub is_ref: bool,
-    // #[br(if(is_ref==1))]
-    pub ref_type: Ref,
-    // #[br(if(is_ref==1))]
-    // sdacl_attr: SDACL,
-    // #[br(if(is_ref==0), calc(n.0 & 7))]
-    // alpow: u16,
-    // #[br(if(is_ref==0 && alpow != 0), calc(1 << (alpow - 1)))]
-    pub effective_alignment: u16,
-    // #[br(if(is_ref==0))]
-    pub taudt_bits: SDACL,
-    // #[br(if(is_ref==0), count=n.0>>3)]
-    pub members: Vec<UnionMember>,
+impl IdbParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn skip_id0(mut self) -> Self {
+        self.skip_id0 = true;
+        self
+    }
+
+    pub fn skip_id1(mut self) -> Self {
+        self.skip_id1 = true;
+        self
+    }
+
+    pub fn skip_nam(mut self) -> Self {
+        self.skip_nam = true;
+        self
+    }
+
+    pub fn skip_seg(mut self) -> Self {
+        self.skip_seg = true;
+        self
+    }
+
+    pub fn skip_til(mut self) -> Self {
+        self.skip_til = true;
+        self
+    }
+
+    pub fn skip_id2(mut self) -> Self {
+        self.skip_id2 = true;
+        self
+    }
+
+    /// Fails on types of the TIL section nested deeper than `max` levels,
+    /// a pointer to `int` being two. Defaults to 64.
+    pub fn max_recursion(mut self, max: u32) -> Self {
+        self.depth = TypeDepth::new(max);
+        self
+    }
+
+    /// Keeps the zlib stream of each zipped TIL bucket next to its inflated
+    /// data, see [`TILBucketZip::compressed_data`].
+    pub fn keep_compressed_data(mut self) -> Self {
+        self.keep_compressed = true;
+        self
+    }
+
+    pub fn parse(&self, bytes: &[u8]) -> BinResult<IDB> {
+        trace_span!("idb", len = bytes.len());
+        self.parse_from_reader(binrw::io::Cursor::new(bytes))
+    }
+
+    /// Parses from a reader positioned at the start of the database, seeking
+    /// to each section instead of buffering the whole file.
+    pub fn parse_from_reader<R: Read + Seek>(&self, mut reader: R) -> BinResult<IDB> {
+        reader.read_ne_args((*self,))
+    }
+}
+
+#[derive(BinRead, Debug)]
+#[br(import(parser: IdbParser))]
+pub struct IDB {
+    header: IDBHeader,
+    #[br(seek_before = SeekFrom::Start(header.id0_offset), if(header.id0_offset != 0 && !parser.skip_id0))]
+    pub id0: Option<ID0Section>,
+    #[br(seek_before = SeekFrom::Start(header.id1_offset), if(header.id1_offset != 0 && !parser.skip_id1))]
+    #[br(args(header.is_64()))]
+    pub id1: Option<ID1Section>,
+    #[br(seek_before = SeekFrom::Start(header.nam_offset), if(header.nam_offset != 0 && !parser.skip_nam))]
+    nam: Option<NAMSection>,
+    /// Read from `id0`, so `None` when that is skipped.
+    #[br(calc = if parser.skip_seg { None } else { id0.as_ref().and_then(|id0| SEGSection::from_id0(id0, header.word_size())) })]
+    pub seg: Option<SEGSection>,
+    #[br(seek_before = SeekFrom::Start(header.til_offset), if(header.til_offset != 0 && !parser.skip_til))]
+    #[br(parse_with = read_til_section, args(parser.depth, parser.keep_compressed))]
+    pub til: Option<TILSection>,
+    #[br(seek_before = SeekFrom::Start(header.id2_offset), if(header.id2_offset != 0 && !parser.skip_id2))]
+    pub id2: Option<ID2Section>,
 }
 
-impl BinRead for Struct {
-    type Args = ();
+/// Reads the TIL section of an IDB, inflating it first when it is
+/// compressed.
+fn read_til_section<R: Read + Seek>(
+    reader: &mut R,
+    _: &ReadOptions,
+    (depth, keep_compressed): (TypeDepth, bool),
+) -> BinResult<Option<TILSection>> {
+    let pos = reader.stream_position()?;
+    let header = reader.read_ne::<IDBSectionHeader>()?;
+    reader.seek(SeekFrom::Start(pos))?;
+    match header.compression() {
+        Ok(CompressionMethod::None) => reader
+            .read_ne_args((false, false, depth, keep_compressed))
+            .map(Some),
+        Ok(CompressionMethod::Zlib) => {
+            let (header, data) = read_section_body(reader)?;
+            let mut section: TILSection = binrw::io::Cursor::new(data.as_slice())
+                .read_ne_args((true, false, depth, keep_compressed))?;
+            section.header = header;
+            Ok(Some(section))
+        }
+        Err(err) => Err(binrw::Error::Custom {
+            pos,
+            err: Box::new(err),
+        }),
+    }
+}
+
+impl TILSection {
+    pub fn parse(bytes: &[u8]) -> BinResult<Self> {
+        trace_span!("til_section", len = bytes.len());
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        cursor.read_ne_args((true, false, TypeDepth::default(), false))
+    }
+
+    /// Parses like [`TILSection::parse`], additionally timing each bucket.
+    pub fn parse_with_stats(bytes: &[u8]) -> BinResult<(Self, ParseStats)> {
+        trace_span!("til_section", len = bytes.len());
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        let section: Self = cursor.read_ne_args((true, true, TypeDepth::default(), false))?;
+        let stats = ParseStats {
+            symbols: section.symbols.stats(),
+            types: section.types.stats(),
+        };
+        Ok((section, stats))
+    }
+
+    /// Parses like [`TILSection::parse`], additionally keeping the zlib
+    /// stream of each zipped bucket so that [`TILBucketZip::to_bytes`]
+    /// reproduces it exactly.
+    pub fn parse_with_compressed_data(bytes: &[u8]) -> BinResult<Self> {
+        trace_span!("til_section", len = bytes.len());
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        cursor.read_ne_args((true, false, TypeDepth::default(), true))
+    }
+
+    /// Parses from a reader positioned at the start of a standalone .til.
+    pub fn parse_from_reader<R: Read + Seek>(mut reader: R) -> BinResult<Self> {
+        reader.read_ne_args((true, false, TypeDepth::default(), false))
+    }
+
+    pub fn parse_from_file(path: String) -> BinResult<Self> {
+        let file = File::open(path)?;
+        TILSection::parse_from_reader(std::io::BufReader::new(file))
+    }
+
+    /// Finds the type a typedef refers to, by ordinal for ordinal references
+    /// and by name otherwise.
+    fn find_typedef(&self, typedef: &Typedef) -> Option<&TILTypeInfo> {
+        if typedef.is_ordref {
+            self.get_type_by_ordinal(typedef.ordinal.0 as u64)
+        } else {
+            self.get_type(&typedef.name)
+        }
+    }
+
+    /// The lookup index over the types bucket, built on first use.
+    fn index(&self) -> &TypeIndex {
+        self.index
+            .get_or_init(|| TypeIndex::new(self.types.type_info()))
+    }
+
+    /// Drops the lookup index used by [`TILSection::get_type`] and
+    /// [`TILSection::get_type_by_ordinal`] so the next lookup rebuilds it.
+    /// Call this after modifying `types`. Until then, lookups of entries that
+    /// moved fall back to scanning the bucket, and entries that were added or
+    /// renamed are missed.
+    pub fn invalidate_index(&mut self) {
+        self.index.take();
+    }
+
+    /// The types bucket entry at the indexed `position` when `matches` holds
+    /// for it, otherwise the first one it holds for, as the bucket changed
+    /// since the index was built.
+    fn indexed_type(
+        &self,
+        position: usize,
+        matches: impl Fn(&TILTypeInfo) -> bool,
+    ) -> Option<&TILTypeInfo> {
+        let type_info = self.types.type_info();
+        match type_info.get(position) {
+            Some(info) if matches(info) => Some(info),
+            _ => type_info.iter().find(|info| matches(info)),
+        }
+    }
+
+    /// Looks up a types bucket entry by its name.
+    pub fn get_type(&self, name: &str) -> Option<&TILTypeInfo> {
+        let position = *self.index().by_name.get(name.as_bytes())?;
+        self.indexed_type(position, |info| info.name.0 == name.as_bytes())
+    }
+
+    /// Every entry of the types bucket as a JSON array.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&self.iter_types().collect::<Vec<_>>())
+    }
+
+    /// True when the buckets are zlib compressed, TIL_ZIP.
+    pub fn is_zipped(&self) -> bool {
+        self.flags & TIL_ZIP != 0
+    }
+
+    /// True when the header records the number of ordinals, TIL_ORD.
+    pub fn has_ordinals(&self) -> bool {
+        self.flags & TIL_ORD != 0
+    }
+
+    /// True for a til usable with any compiler, TIL_UNI. Names are stored
+    /// the same either way.
+    pub fn is_universal(&self) -> bool {
+        self.flags & TIL_UNI != 0
+    }
+
+    /// True when the section has a macros bucket, TIL_MAC.
+    pub fn is_mac(&self) -> bool {
+        self.flags & TIL_MAC != 0
+    }
+
+    /// The entries of the types bucket, whether or not it is compressed.
+    pub fn iter_types(&self) -> impl Iterator<Item = &TILTypeInfo> {
+        self.types.type_info().iter()
+    }
+
+    /// The entries of the symbols bucket, whether or not it is compressed.
+    pub fn iter_symbols(&self) -> impl Iterator<Item = &TILTypeInfo> {
+        self.symbols.type_info().iter()
+    }
+
+    /// Indexes both buckets of this section by name and ordinal.
+    pub fn catalog(&self) -> TypeCatalog<'_> {
+        let mut catalog = TypeCatalog::default();
+        catalog.add(self);
+        catalog
+    }
+
+    /// Looks up a types bucket entry by its ordinal.
+    pub fn get_type_by_ordinal(&self, ordinal: u64) -> Option<&TILTypeInfo> {
+        let position = *self.index().by_ordinal.get(&ordinal)?;
+        self.indexed_type(position, |info| info.ordinal.value() == ordinal)
+    }
+
+    /// Follows typedefs, type references and single member structs/unions down
+    /// to the primitive type they are built from. Enums resolve to their base
+    /// type. Returns `None` for other aggregates, unresolvable typedefs and cycles.
+    pub fn resolve_to_primitive<'a>(&'a self, typ: &'a Types) -> Option<TypeMetadata> {
+        let mut seen: Vec<&TILTypeInfo> = Vec::new();
+        let mut typ = typ;
+        loop {
+            typ = match typ {
+                Types::Unset(metadata) => return Some(metadata.clone()),
+                Types::Enum(enum_type) => match enum_type.base_type() {
+                    Types::Unset(metadata) => return Some(metadata),
+                    _ => return None,
+                },
+                Types::Typedef(typedef) => {
+                    let info = self.find_typedef(typedef)?;
+                    if seen.iter().any(|seen| std::ptr::eq(*seen, info)) {
+                        return None;
+                    }
+                    seen.push(info);
+                    &info.tinfo
+                }
+                Types::Struct(struct_type) if struct_type.is_ref => &struct_type.ref_type.0,
+                Types::Struct(struct_type) if struct_type.members.len() == 1 => {
+                    &struct_type.members[0].0
+                }
+                Types::Union(union_type) if union_type.is_ref => &union_type.ref_type.0,
+                Types::Union(union_type) if union_type.members.len() == 1 => {
+                    &union_type.members[0].0
+                }
+                _ => return None,
+            };
+        }
+    }
+
+    /// Near or far pointer size from the memory model bits of `cm`.
+    fn pointer_size(&self, far: bool) -> Option<u64> {
+        match (self.cm & 0x03, far) {
+            (0x01, _) if self.size_i > 2 => Some(8),
+            (0x01, false) => Some(1),
+            (0x01, true) => Some(2),
+            (0x02, false) => Some(2),
+            (0x02, true) => Some(4),
+            (0x03, false) => Some(4),
+            (0x03, true) => Some(6),
+            _ => None,
+        }
+    }
+
+    fn primitive_size(&self, metadata: &TypeMetadata) -> Option<u64> {
+        self.sizeof_basic(metadata.get_base_type_flag().0, metadata.get_type_flag().0)
+    }
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let mut n = reader.read_ne::<DT>()?.0 as u32;
-        let mut res = Self::default();
-        res.metadata = metadata;
-        if n == 0 {
-            res.is_ref = true;
-            res.ref_type = reader.read_ne::<Ref>()?;
-            res.taudt_bits = reader.read_ne::<SDACL>()?;
-        } else {
-            if n == 0x7FFE {
-                n = reader.read_ne::<DE>()?.0;
+    /// The size in bytes of a basic type, given its base type (the low four
+    /// bits of the type byte) and type flag (bits 0x30), using this section's
+    /// `int`, `bool` and `long double` sizes. Returns `None` for `void`,
+    /// unsized unknown types and sizes the section doesn't record. Alignment
+    /// isn't part of the size, structs apply `def_align` when laying out.
+    pub fn sizeof_basic(&self, base: u8, flag: u8) -> Option<u64> {
+        let type_flag = flag & 0x30;
+        match base & 0x0F {
+            // BT_UNK and BT_VOID carry their size in the type flag bits
+            0x00 => match type_flag {
+                0x10 => Some(2),
+                0x20 => Some(8),
+                _ => None,
+            },
+            0x01 => match type_flag {
+                0x10 => Some(1),
+                0x20 => Some(4),
+                0x30 => Some(16),
+                _ => None,
+            },
+            0x02 => Some(1),
+            0x03 => Some(2),
+            0x04 => Some(4),
+            0x05 => Some(8),
+            0x06 => Some(16),
+            0x07 => Some(self.size_i as u64),
+            0x08 => match type_flag {
+                0x00 => Some(self.size_b as u64),
+                0x10 => Some(1),
+                0x20 => Some(2),
+                _ => Some(4),
+            },
+            // BT_FLOAT, BTMT_SPECFLT depends on the processor's tbyte size
+            0x09 => match type_flag {
+                0x00 => Some(4),
+                0x10 => Some(8),
+                0x20 => self.size_ldbl.map(u64::from),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Caps a member's alignment at the struct's packing, if it has one.
+    fn pack_alignment(&self, align: u64, effective_alignment: u16) -> u64 {
+        let pack = match effective_alignment {
+            0 => self.def_align as u64,
+            pack => pack as u64,
+        };
+        match pack {
+            0 => align.max(1),
+            pack => align.clamp(1, pack),
+        }
+    }
+
+    /// Size and alignment of a type in bytes.
+    fn size_align(&self, typ: &Types, depth: u32) -> Option<(u64, u64)> {
+        if depth > MAX_TYPE_DEPTH {
+            return None;
+        }
+        match typ {
+            Types::Unset(metadata) => {
+                let size = self.primitive_size(metadata)?;
+                Some((size, size))
             }
-            let alpow = n & 7;
-            let mem_cnt = n >> 3;
-            if alpow == 0 {
-                res.effective_alignment = 0;
-            } else {
-                res.effective_alignment = 1 << (alpow - 1);
+            Types::Pointer(pointer) => {
+                let size = match pointer.metadata.get_type_flag().0 {
+                    0x20 => self.pointer_size(true)?,
+                    0x30 if pointer.closure.is_none() && pointer.based_ptr_size != 0 => {
+                        pointer.based_ptr_size as u64
+                    }
+                    0x30 => return None,
+                    _ => self.pointer_size(false)?,
+                };
+                Some((size, size))
             }
-            res.taudt_bits = reader.read_ne::<SDACL>()?;
-            let mut vec: Vec<StructMember> = Vec::new();
-            for _ in 0..mem_cnt {
-                vec.push(reader.read_ne::<StructMember>()?);
+            Types::Array(array) => {
+                let (size, align) = self.size_align(&array.elem_type, depth + 1)?;
+                Some((size * array.nelem as u64, align))
             }
-            res.members = vec;
+            Types::Typedef(typedef) => {
+                self.size_align(&self.find_typedef(typedef)?.tinfo, depth + 1)
+            }
+            Types::Struct(struct_type) if struct_type.is_ref => {
+                self.size_align(&struct_type.ref_type.0, depth + 1)
+            }
+            Types::Struct(struct_type) => {
+                let (_, size, align) = self.struct_offsets(struct_type, depth)?;
+                Some((size, align))
+            }
+            Types::Union(union_type) if union_type.is_ref => {
+                self.size_align(&union_type.ref_type.0, depth + 1)
+            }
+            Types::Union(union_type) => {
+                let mut size = 0;
+                let mut align = 1;
+                for member in &union_type.members {
+                    let (member_size, member_align) = self.size_align(&member.0, depth + 1)?;
+                    size = size.max(member_size);
+                    align = align
+                        .max(self.pack_alignment(member_align, union_type.effective_alignment));
+                }
+                Some((round_up(size, align), align))
+            }
+            Types::Enum(enum_type) if enum_type.is_ref => {
+                self.size_align(&enum_type.ref_type.0, depth + 1)
+            }
+            Types::Enum(enum_type) => {
+                let size = match enum_type.bytesize {
+                    0 => self.size_e as u64,
+                    size => size,
+                };
+                Some((size, size))
+            }
+            Types::Bitfield(bitfield) => {
+                let size = bitfield.nbytes as u64;
+                Some((size, size))
+            }
+            _ => None,
         }
-        Ok(res)
     }
-}
-
-impl BinRead for Union {
-    type Args = ();
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let mut n = reader.read_ne::<DT>()?.0 as u32;
-        let mut res = Self::default();
-        res.metadata = metadata;
-        if n == 0 {
-            res.is_ref = true;
-            res.ref_type = reader.read_ne::<Ref>()?;
-            res.taudt_bits = reader.read_ne::<SDACL>()?;
-        } else {
-            if n == 0x7FFE {
-                n = reader.read_ne::<DE>()?.0;
+    /// Offset and size of each member, then the struct's size and alignment.
+    /// Consecutive bitfields of the same size share a storage unit while their
+    /// widths fit in it.
+    fn struct_offsets(
+        &self,
+        struct_type: &Struct,
+        depth: u32,
+    ) -> Option<(Vec<MemberOffset>, u64, u64)> {
+        let mut fields = Vec::with_capacity(struct_type.members.len());
+        let mut offset = 0;
+        let mut struct_align = 1;
+        // The offset, size and used bits of the open bitfield storage unit
+        let mut unit: Option<(u64, u64, u64)> = None;
+        for member in &struct_type.members {
+            let (size, align) = self.size_align(&member.0, depth + 1)?;
+            let width = match &member.0 {
+                Types::Bitfield(bitfield) => Some(bitfield.width as u64),
+                _ => None,
+            };
+            match (width, unit) {
+                (Some(width), Some((unit_offset, unit_size, used)))
+                    if unit_size == size && used + width <= size * 8 =>
+                {
+                    unit = Some((unit_offset, unit_size, used + width));
+                    fields.push((unit_offset, size));
+                    continue;
+                }
+                _ => {}
             }
-            let alpow = n & 7;
-            let mem_cnt = n >> 3;
-            if alpow == 0 {
-                res.effective_alignment = 0;
-            } else {
-                res.effective_alignment = 1 << (alpow - 1);
+
+            let align = self.pack_alignment(align, struct_type.effective_alignment);
+            offset = round_up(offset, align);
+            unit = width.map(|width| (offset, size, width));
+            fields.push((offset, size));
+            offset += size;
+            struct_align = struct_align.max(align);
+        }
+        Some((fields, round_up(offset, struct_align), struct_align))
+    }
+
+    /// The name, offset and size of each member of a struct entry, following
+    /// typedefs and struct references to the definition. Bitfields report the
+    /// storage unit they are packed into. Returns `None` for other types and
+    /// when a member's size can't be determined.
+    pub fn struct_layout(&self, info: &TILTypeInfo) -> Option<Vec<(String, u64, u64)>> {
+        let mut info = info;
+        let mut depth = 0;
+        let struct_type = loop {
+            let typedef = match &info.tinfo {
+                Types::Struct(struct_type) if !struct_type.is_ref => break struct_type,
+                Types::Struct(struct_type) => match &struct_type.ref_type.0 {
+                    Types::Typedef(typedef) => typedef,
+                    _ => return None,
+                },
+                Types::Typedef(typedef) => typedef,
+                _ => return None,
+            };
+            info = self.find_typedef(typedef)?;
+            depth += 1;
+            if depth > MAX_TYPE_DEPTH {
+                return None;
             }
-            res.taudt_bits = reader.read_ne::<SDACL>()?;
-            let mut vec: Vec<UnionMember> = Vec::new();
-            for _ in 0..mem_cnt {
-                vec.push(reader.read_ne::<UnionMember>()?);
+        };
+
+        let (offsets, _, _) = self.struct_offsets(struct_type, depth)?;
+        let layout = offsets
+            .into_iter()
+            .enumerate()
+            .map(|(index, (offset, size))| {
+                let name = match info.fields.0.get(index) {
+                    Some(name) => name.clone(),
+                    None => format!("field_{:X}", offset),
+                };
+                (name, offset, size)
+            })
+            .collect();
+        Some(layout)
+    }
+
+    /// True for 8 bit integer types and the wide character typedefs.
+    fn is_char_type(&self, typ: &Types) -> bool {
+        match typ {
+            Types::Typedef(typedef)
+                if matches!(typedef.name.as_str(), "wchar_t" | "char16_t" | "char32_t") =>
+            {
+                true
             }
-            res.members = vec;
+            Types::Unset(_) | Types::Typedef(_) => matches!(
+                self.resolve_to_primitive(typ),
+                Some(metadata) if metadata.get_base_type_flag().0 == 0x02
+            ),
+            _ => false,
         }
-        Ok(res)
     }
-}
 
-#[derive(Clone, Default, Debug)]
-pub struct EnumMember(pub u64);
+    /// Returns the types bucket entries that are arrays of, or pointers to,
+    /// characters, which is how string buffers are declared.
+    pub fn string_types(&self) -> Vec<&TILTypeInfo> {
+        self.types
+            .type_info()
+            .iter()
+            .filter(|info| match &info.tinfo {
+                Types::Array(array) => self.is_char_type(&array.elem_type),
+                Types::Pointer(pointer) => self.is_char_type(&pointer.typ),
+                _ => false,
+            })
+            .collect()
+    }
 
-#[derive(Clone, Default, Debug)]
-pub struct Enum {
-    pub metadata: TypeMetadata,
-    pub group_sizes: Vec<DT>,
-    pub taenum_bits: TypeAttribute,
-    pub bte: u8,
-    pub members: Vec<EnumMember>,
-    pub ref_type: Ref,
-    pub is_ref: bool,
-    pub bytesize: u64,
-}
-impl BinRead for Enum {
-    type Args = (u8,);
+    /// The C name a typedef refers to. Entries defining a struct, union or
+    /// enum are spelled with their tag so the output is valid C.
+    fn c_type_name(&self, typedef: &Typedef) -> String {
+        match self.find_typedef(typedef) {
+            Some(info) => {
+                let tag = match &info.tinfo {
+                    Types::Struct(struct_type) if !struct_type.is_ref => "struct ",
+                    Types::Union(union_type) if !union_type.is_ref => "union ",
+                    Types::Enum(enum_type) if !enum_type.is_ref => "enum ",
+                    _ => "",
+                };
+                format!("{}{}", tag, info.name_str())
+            }
+            None if typedef.is_ordref => format!("#{}", typedef.ordinal.0),
+            None => typedef.name.clone(),
+        }
+    }
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        args: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let mut n = reader.read_ne::<DT>()?.0 as u32;
-        let mut is_ref = false;
-        if n == 0 {
-            let ref_type = reader.read_ne::<Ref>()?;
-            let taenum_bits = reader.read_ne::<SDACL>()?.0;
-            is_ref = true;
-            return Ok(Enum {
-                metadata,
-                ref_type,
-                taenum_bits,
-                is_ref,
-                ..Default::default()
-            });
-        } else {
-            if n == 0x7FFE {
-                n = reader.read_ne::<DE>()?.0;
+    /// The C declaration of `declarator` with type `typ`, for example
+    /// `int (*name)[4]`. An empty declarator gives the type's name.
+    /// Function arguments are named `a1`, `a2`, and so on.
+    pub fn c_declaration(&self, typ: &Types, declarator: &str) -> String {
+        self.c_declaration_depth(typ, declarator, None, 0)
+    }
+
+    /// Like `c_declaration`, with the function arguments named after the
+    /// fields of `info` when the declared type is a function, or a pointer
+    /// to one.
+    fn c_declaration_depth(
+        &self,
+        typ: &Types,
+        declarator: &str,
+        info: Option<&TILTypeInfo>,
+        depth: u32,
+    ) -> String {
+        let join = |name: String| {
+            if declarator.is_empty() {
+                name
+            } else {
+                format!("{} {}", name, declarator)
             }
-            let taenum_bits = reader.read_ne::<TAH>()?.0;
-            let bte = reader.read_ne::<u8>()?;
-            let mut cur: u64 = 0;
-            let mut hi = DE::default();
-            let mut bytesize = 0;
-            let mask: u64 = {
-                let emsize = bte & 0x07;
-                let mut bitsize = 0_u64;
-                if emsize != 0 {
-                    bytesize = 1 << (emsize - 1);
-                } else if args.0 != 0 {
-                    bytesize = args.0 as u64;
-                } else {
-                    bytesize = 4;
+        };
+        // Array and function declarators bind tighter than pointers
+        let wrap = || {
+            if declarator.starts_with('*') {
+                format!("({})", declarator)
+            } else {
+                declarator.to_string()
+            }
+        };
+        if depth > MAX_TYPE_DEPTH {
+            return join("_UNKNOWN".to_string());
+        }
+        match typ {
+            Types::Unset(metadata) => join(c_modifiers(metadata) + &c_primitive_name(metadata)),
+            Types::Typedef(typedef) => {
+                join(c_modifiers(&typedef.metadata) + &self.c_type_name(typedef))
+            }
+            Types::Pointer(pointer) => {
+                let declarator = format!("*{}{}", c_modifiers(&pointer.metadata), declarator);
+                self.c_declaration_depth(&pointer.typ, declarator.trim_end(), info, depth + 1)
+            }
+            Types::Array(array) => {
+                let declarator = match array.nelem {
+                    0 => format!("{}[]", wrap()),
+                    nelem => format!("{}[{}]", wrap(), nelem),
+                };
+                self.c_declaration_depth(&array.elem_type, &declarator, info, depth + 1)
+            }
+            Types::Function(function) => {
+                let mut args = function
+                    .args
+                    .iter()
+                    .enumerate()
+                    .map(|(index, arg)| {
+                        let name = match info.and_then(|info| info.fields.0.get(index)) {
+                            Some(name) => name.clone(),
+                            None => format!("a{}", index + 1),
+                        };
+                        self.c_declaration_depth(&arg.0, &name, None, depth + 1)
+                    })
+                    .collect::<Vec<_>>();
+                if function.is_variadic() {
+                    args.push("...".to_string());
+                } else if args.is_empty() {
+                    args.push("void".to_string());
                 }
-                bitsize = bytesize * 8;
-                if bitsize < 64 {
-                    (1 << bitsize) - 1
+                // The calling convention goes inside the parentheses of a
+                // pointer declarator, `int (__cdecl *name)(int a1)`
+                let declarator = match c_calling_convention(function.calling_convention()) {
+                    Some(cc) if declarator.starts_with('*') => format!("({} {})", cc, declarator),
+                    Some(cc) if declarator.is_empty() => cc.to_string(),
+                    Some(cc) => format!("{} {}", cc, declarator),
+                    None => wrap(),
+                };
+                let declarator = format!("{}({})", declarator, args.join(", "));
+                self.c_declaration_depth(&function.ret, &declarator, None, depth + 1)
+            }
+            Types::Struct(struct_type) if struct_type.is_ref => {
+                self.c_tagged_ref("struct", &struct_type.ref_type, declarator, depth)
+            }
+            Types::Union(union_type) if union_type.is_ref => {
+                self.c_tagged_ref("union", &union_type.ref_type, declarator, depth)
+            }
+            Types::Enum(enum_type) if enum_type.is_ref => {
+                self.c_tagged_ref("enum", &enum_type.ref_type, declarator, depth)
+            }
+            Types::Struct(struct_type) => {
+                let members = struct_type.members.iter().map(|member| &member.0);
+                join(format!("struct {}", self.c_body(members, None, depth)))
+            }
+            Types::Union(union_type) => {
+                let members = union_type.members.iter().map(|member| &member.0);
+                join(format!("union {}", self.c_body(members, None, depth)))
+            }
+            Types::Enum(enum_type) => join(format!("enum {}", c_enum_body(enum_type, None))),
+            Types::Bitfield(bitfield) => {
+                let sign = if bitfield.unsigned { "unsigned " } else { "" };
+                let storage = match bitfield.nbytes {
+                    1 => "char",
+                    2 => "short",
+                    4 => "int",
+                    _ => "__int64",
+                };
+                format!("{}{} {} : {}", sign, storage, declarator, bitfield.width)
+            }
+            Types::Reserved(_) | Types::Unknown(_) => join("_UNKNOWN".to_string()),
+        }
+    }
+
+    fn c_tagged_ref(&self, tag: &str, ref_type: &Ref, declarator: &str, depth: u32) -> String {
+        match &ref_type.0 {
+            Types::Typedef(typedef) => {
+                let name = match self.find_typedef(typedef) {
+                    Some(info) => info.name_str().into_owned(),
+                    None => self.c_type_name(typedef),
+                };
+                let name = format!("{} {}", tag, name);
+                if declarator.is_empty() {
+                    name
                 } else {
-                    0xFFFFFFFFFFFFFFFF
-                }
-            };
-            let mut group_sizes = Vec::<DT>::new();
-            let mut members = Vec::<EnumMember>::new();
-            for _ in 0..n {
-                let lo = reader.read_ne::<DE>()?;
-                if (taenum_bits.0 & 0x0020) > 0 {
-                    hi = reader.read_ne::<DE>()?;
-                }
-                if (bte & 0x10) > 0 {
-                    group_sizes.push(reader.read_ne::<DT>()?);
+                    format!("{} {}", name, declarator)
                 }
-                cur = cur
-                    .overflowing_add((lo.0 as u64) | ((hi.0 as u64) << 32) & mask)
-                    .0;
-                // cur += (lo.0 as u64) | ((hi.0 as u64) << 32) & mask;
-                members.push(EnumMember(cur));
             }
-            return Ok(Enum {
-                metadata,
-                group_sizes,
-                taenum_bits,
-                bte,
-                members,
-                is_ref,
-                bytesize,
-                ..Default::default()
-            });
+            typ => self.c_declaration_depth(typ, declarator, None, depth + 1),
         }
-        Ok(Default::default())
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct Bitfield {
-    metadata: TypeMetadata,
-    pub unsigned: bool,
-    pub width: u16,
-    pub nbytes: i32,
-}
-
-impl BinRead for Bitfield {
-    type Args = ();
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = reader.read_ne::<TypeMetadata>()?;
-        let nbytes = 1 << (metadata.get_type_flag().0 >> 4);
-        let dt = reader.read_ne::<DT>()?;
-        let width = &dt.0 >> 1;
-        let unsigned = (&dt.0 & 1) > 0;
-        let tah = reader.read_ne::<TAH>()?;
-        Ok(Self {
-            metadata,
-            unsigned,
-            width,
-            nbytes,
-        })
+    /// The braced member list of a struct or union.
+    fn c_body<'a>(
+        &self,
+        members: impl Iterator<Item = &'a Types>,
+        info: Option<&TILTypeInfo>,
+        depth: u32,
+    ) -> String {
+        let mut body = "{\n".to_string();
+        for (index, member) in members.enumerate() {
+            let name = match info.and_then(|info| info.fields.0.get(index)) {
+                Some(name) => name.clone(),
+                None => format!("field_{}", index),
+            };
+            body += &format!(
+                "  {};{}\n",
+                self.c_declaration_depth(member, &name, None, depth + 1),
+                c_field_comment(info, index)
+            );
+        }
+        body + "}"
     }
-}
 
-impl BinRead for Types {
-    type Args = (u8,);
-
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        args: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let metadata = TypeMetadata(reader.read_ne()?);
-        if metadata.get_base_type_flag().is_typeid_last()
-            || metadata.get_base_type_flag().is_reserved()
-        {
-            // reader.seek(SeekFrom::Current(1));
-            Ok(Types::Unset(metadata))
-        } else {
-            reader.seek(SeekFrom::Current(-1));
-            let mut collect_rest = || {
-                reader
-                    .bytes()
-                    .take_while(|x| !matches!(x, Ok(0)))
-                    .map(|x| x.unwrap())
-                    .collect::<Vec<u8>>()
-            };
+    /// The C definition of a types bucket entry, after its comment if it
+    /// has one.
+    fn c_definition(&self, info: &TILTypeInfo) -> String {
+        let name = info.name_str();
+        let mut comment = String::new();
+        for line in info.comment().lines() {
+            comment += &format!("// {}\n", line);
+        }
+        let definition = match &info.tinfo {
+            Types::Struct(struct_type) if !struct_type.is_ref => {
+                let members = struct_type.members.iter().map(|member| &member.0);
+                format!("struct {} {};", name, self.c_body(members, Some(info), 0))
+            }
+            Types::Union(union_type) if !union_type.is_ref => {
+                let members = union_type.members.iter().map(|member| &member.0);
+                format!("union {} {};", name, self.c_body(members, Some(info), 0))
+            }
+            Types::Enum(enum_type) if !enum_type.is_ref => {
+                format!("enum {} {};", name, c_enum_body(enum_type, Some(info)))
+            }
+            typ => format!(
+                "typedef {};",
+                self.c_declaration_depth(typ, &name, Some(info), 0)
+            ),
+        };
+        comment + &definition
+    }
 
-            if metadata.get_base_type_flag().is_pointer() {
-                Ok(Types::Pointer(Box::new(reader.read_ne()?)))
-            } else if metadata.get_base_type_flag().is_function() {
-                Ok(Types::Function(Box::new(reader.read_ne()?)))
-            } else if metadata.get_base_type_flag().is_array() {
-                Ok(Types::Array(Box::new(reader.read_ne()?)))
-            } else if metadata.get_full_type_flag().is_typedef() {
-                Ok(Types::Typedef(reader.read_ne()?))
-            } else if metadata.get_full_type_flag().is_union() {
-                Ok(Types::Union(Box::new(reader.read_ne()?)))
-            } else if metadata.get_full_type_flag().is_struct() {
-                Ok(Types::Struct(Box::new(reader.read_ne()?)))
-            } else if metadata.get_full_type_flag().is_enum() {
-                Ok(Types::Enum(Box::new(reader.read_ne_args(args)?)))
-            } else if metadata.get_base_type_flag().is_bitfield() {
-                Ok(Types::Bitfield(reader.read_ne()?))
-            } else {
-                Ok(Types::Unknown(collect_rest()))
+    /// The types bucket entries `typ` refers to, each with whether its
+    /// definition has to come first. Only `by_value` uses of a struct or
+    /// union need one, behind a pointer its tag being declared is enough.
+    /// Typedef names always need their definition.
+    fn c_dependencies<'a>(
+        &'a self,
+        typ: &'a Types,
+        by_value: bool,
+        deps: &mut Vec<(&'a TILTypeInfo, bool)>,
+    ) {
+        match typ {
+            Types::Typedef(typedef) => {
+                if let Some(info) = self.find_typedef(typedef) {
+                    let tagged = match &info.tinfo {
+                        Types::Struct(struct_type) => !struct_type.is_ref,
+                        Types::Union(union_type) => !union_type.is_ref,
+                        _ => false,
+                    };
+                    deps.push((info, by_value || !tagged));
+                }
+            }
+            Types::Pointer(pointer) => self.c_dependencies(&pointer.typ, false, deps),
+            Types::Array(array) => self.c_dependencies(&array.elem_type, by_value, deps),
+            Types::Function(function) => {
+                self.c_dependencies(&function.ret, false, deps);
+                for arg in &function.args {
+                    self.c_dependencies(&arg.0, false, deps);
+                }
+            }
+            Types::Struct(struct_type) if struct_type.is_ref => {
+                self.c_dependencies(&struct_type.ref_type.0, by_value, deps)
+            }
+            Types::Struct(struct_type) => {
+                for member in &struct_type.members {
+                    self.c_dependencies(&member.0, by_value, deps);
+                }
+            }
+            Types::Union(union_type) if union_type.is_ref => {
+                self.c_dependencies(&union_type.ref_type.0, by_value, deps)
+            }
+            Types::Union(union_type) => {
+                for member in &union_type.members {
+                    self.c_dependencies(&member.0, by_value, deps);
+                }
+            }
+            Types::Enum(enum_type) if enum_type.is_ref => {
+                self.c_dependencies(&enum_type.ref_type.0, by_value, deps)
             }
+            _ => {}
         }
     }
-}
 
-impl BinRead for NullVecLenString {
-    type Args = ();
+    /// Declares every type of the types bucket in C, each after the
+    /// definitions it depends on, followed by `extern` declarations of the
+    /// symbols. Structs and unions that are used through pointers before
+    /// their definition are forward declared at the top.
+    pub fn to_c_header(&self) -> String {
+        let types = self.types.type_info();
+        let index_of =
+            |info: &TILTypeInfo| types.iter().position(|other| std::ptr::eq(other, info));
+        let mut order = (0..types.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| types[index].ordinal.value());
+        // The entries each one needs defined first, and those it only needs
+        // declared
+        let (needs_definition, needs_declaration): (Vec<Vec<_>>, Vec<Vec<_>>) = types
+            .iter()
+            .map(|info| {
+                let mut deps = Vec::new();
+                self.c_dependencies(&info.tinfo, true, &mut deps);
+                let (definition, declaration): (Vec<_>, Vec<_>) =
+                    deps.into_iter().partition(|(_, definition)| *definition);
+                let indices = |deps: Vec<(&TILTypeInfo, bool)>| {
+                    deps.into_iter()
+                        .filter_map(|(info, _)| index_of(info))
+                        .collect::<Vec<_>>()
+                };
+                (indices(definition), indices(declaration))
+            })
+            .unzip();
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        _: &binrw::ReadOptions,
-        _: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let vec = reader
-            .bytes()
-            .take_while(|x| !matches!(x, Ok(0)))
-            .map(|x| x.unwrap())
-            .collect::<Vec<u8>>();
+        // Depth first, emitting an entry once everything it uses is emitted
+        let mut emitted = vec![false; types.len()];
+        let mut visiting = vec![false; types.len()];
+        let mut forward = Vec::new();
+        let mut definitions = Vec::new();
+        for &root in &order {
+            let mut stack = vec![(root, None::<Vec<usize>>)];
+            while let Some((index, deps)) = stack.pop() {
+                if emitted[index] {
+                    continue;
+                }
+                let deps = match deps {
+                    Some(deps) => deps,
+                    None => {
+                        visiting[index] = true;
+                        stack.push((index, Some(needs_definition[index].clone())));
+                        continue;
+                    }
+                };
+                if let Some(next) = deps
+                    .iter()
+                    .copied()
+                    .find(|&dep| !emitted[dep] && dep != index)
+                {
+                    if visiting[next] {
+                        // Definitions containing each other can't be valid C,
+                        // emit them in any order
+                        let deps = deps.into_iter().filter(|&dep| dep != next).collect();
+                        stack.push((index, Some(deps)));
+                    } else {
+                        stack.push((index, Some(deps)));
+                        stack.push((next, None));
+                    }
+                    continue;
+                }
+                for &dep in &needs_declaration[index] {
+                    if !emitted[dep] && dep != index && !forward.contains(&dep) {
+                        forward.push(dep);
+                    }
+                }
+                visiting[index] = false;
+                emitted[index] = true;
+                definitions.push(self.c_definition(&types[index]));
+            }
+        }
 
-        let mut pos = 0;
-        let mut nvec: Vec<String> = Vec::new();
-        while pos < vec.len() {
-            let len = vec[pos];
-            nvec.push(String::from_utf8_lossy(&vec[pos + 1..pos + len as usize]).to_string());
-            pos += len as usize;
+        let mut header = String::new();
+        for index in forward {
+            let tag = match &types[index].tinfo {
+                Types::Struct(_) => "struct",
+                Types::Union(_) => "union",
+                _ => continue,
+            };
+            header += &format!("{} {};\n", tag, types[index].name_str());
+        }
+        for definition in definitions {
+            header += &definition;
+            header.push('\n');
+        }
+        for info in self.symbols.type_info() {
+            header += &format!(
+                "extern {};\n",
+                self.c_declaration_depth(&info.tinfo, &info.name_str(), Some(info), 0)
+            );
         }
+        header
+    }
 
-        Ok(NullVecLenString(nvec))
+    /// Counts the entries of the types and symbols buckets by their kind.
+    pub fn kind_histogram(&self) -> HashMap<TypeKind, usize> {
+        let mut histogram = HashMap::new();
+        for info in self
+            .types
+            .type_info()
+            .iter()
+            .chain(self.symbols.type_info())
+        {
+            *histogram.entry(info.tinfo.kind()).or_insert(0) += 1;
+        }
+        histogram
     }
-}
 
-#[derive(BinRead, Debug, Clone)]
-#[br(import(size_e: u8))]
-pub struct TILTypeInfo {
-    flags: u32,
-    pub name: binrw::NullString,
-    #[br(args { is_u64: (flags >> 31u32) != 0})]
-    pub ordinal: TILOrdinal,
-    #[br(args(size_e), restore_position)]
-    pub tinfo: Types,
-    _info: binrw::NullString,
-    cmt: binrw::NullString,
-    pub fields: NullVecLenString,
-    fieldcmts: binrw::NullString,
-    sclass: u8,
+    /// Returns the raw encoded type of the named entry in the types or
+    /// symbols bucket, which decodes back into the same [`Types`].
+    pub fn type_bytes(&self, name: &str) -> Option<&[u8]> {
+        self.types
+            .type_info()
+            .iter()
+            .chain(self.symbols.type_info())
+            .find(|info| info.name.0 == name.as_bytes())
+            .map(|info| info._info.0.as_slice())
+    }
+
+    /// Serializes the section back into the standalone .til format.
+    ///
+    /// Buckets are rebuilt from their parsed `type_info` and always written
+    /// uncompressed. The macros bucket isn't parsed, so it is dropped. Fails
+    /// when the title, the base or a field name is too long for its length
+    /// byte.
+    pub fn to_bytes(&self) -> BinResult<Vec<u8>> {
+        let flags = self.flags & !(TIL_ZIP | TIL_MAC);
+        let mut bytes = self.signature.as_bytes().to_vec();
+        bytes.extend_from_slice(&self.format.to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        for string in [&self.title, &self.base] {
+            bytes.push(length_byte(string.len(), bytes.len())?);
+            bytes.extend_from_slice(string.as_bytes());
+        }
+        bytes.extend_from_slice(&[
+            self.id,
+            self.cm,
+            self.size_i,
+            self.size_b,
+            self.size_e,
+            self.def_align,
+        ]);
+        bytes.extend(
+            [self.size_s, self.size_l, self.size_ll, self.size_ldbl]
+                .into_iter()
+                .flatten(),
+        );
+        write_type_info(&mut bytes, self.symbols.type_info())?;
+        if let Some(ordinals) = self.type_ordinal_numbers {
+            bytes.extend_from_slice(&ordinals.to_le_bytes());
+        }
+        write_type_info(&mut bytes, self.types.type_info())?;
+        Ok(bytes)
+    }
 }
 
-#[derive(Debug)]
-#[binread]
-#[br(import { size_e: u8 })]
-pub struct TILBucket {
-    pub ndefs: u32,
-    len: u32,
-    #[br(args{ count: ndefs.try_into().unwrap(), inner: (size_e,) }, restore_position)]
-    pub type_info: Vec<TILTypeInfo>,
-    #[br(count = len)]
-    data: Vec<u8>,
+/// The C spelling of a primitive type, using IDA's names for the sized ones.
+fn c_primitive_name(metadata: &TypeMetadata) -> String {
+    let type_flag = metadata.get_type_flag().0;
+    let sign = match type_flag {
+        0x10 => "signed ",
+        0x20 => "unsigned ",
+        _ => "",
+    };
+    match metadata.get_base_type_flag().0 {
+        0x00 => match type_flag {
+            0x10 => "_WORD",
+            0x20 => "_QWORD",
+            _ => "_UNKNOWN",
+        }
+        .to_string(),
+        0x01 => match type_flag {
+            0x10 => "_BYTE",
+            0x20 => "_DWORD",
+            0x30 => "_OWORD",
+            _ => "void",
+        }
+        .to_string(),
+        0x02 if type_flag == 0x30 => "char".to_string(),
+        base @ 0x02..=0x06 => format!("{}__int{}", sign, 8 << (base - 0x02)),
+        0x07 => format!("{}int", sign),
+        0x08 => match type_flag {
+            0x10 => "_BOOL1",
+            0x20 => "_BOOL2",
+            0x30 => "_BOOL4",
+            _ => "bool",
+        }
+        .to_string(),
+        // BT_FLOAT, the type flag is BTMT_FLOAT, DOUBLE, LNGDBL or SPECFLT
+        0x09 => match type_flag {
+            0x10 => "double",
+            0x20 => "long double",
+            0x30 => "_TBYTE",
+            _ => "float",
+        }
+        .to_string(),
+        _ => "_UNKNOWN".to_string(),
+    }
 }
 
-#[derive(Debug)]
-pub struct TILBucketZip {
-    pub ndefs: u32,
-    len: u32,
-    compressed_len: u32,
-    // #[br(args{ count: ndefs.try_into().unwrap(), inner: (size_e,) },restore_position)]
-    pub type_info: Vec<TILTypeInfo>,
-    // #[br(count = compressed_len)]
-    data: Vec<u8>,
+/// The `const`/`volatile` prefix of a type.
+fn c_modifiers(metadata: &TypeMetadata) -> String {
+    let mut modifiers = String::new();
+    if metadata.0 & 0x40 != 0 {
+        modifiers += "const ";
+    }
+    if metadata.0 & 0x80 != 0 {
+        modifiers += "volatile ";
+    }
+    modifiers
 }
 
-impl TILBucketZip {
-    pub fn unzip(&self) -> TILBucket {
-        TILBucket {
-            ndefs: self.ndefs,
-            len: self.len,
-            type_info: self.type_info.clone(),
-            data: self.data.clone(),
-        }
+/// The keyword spelling a calling convention, if it has one.
+fn c_calling_convention(cc: CallingConvention) -> Option<&'static str> {
+    match cc {
+        CallingConvention::Cdecl => Some("__cdecl"),
+        CallingConvention::Stdcall => Some("__stdcall"),
+        CallingConvention::Pascal => Some("__pascal"),
+        CallingConvention::Fastcall => Some("__fastcall"),
+        CallingConvention::Thiscall => Some("__thiscall"),
+        CallingConvention::Golang => Some("__golang"),
+        CallingConvention::Usercall => Some("__usercall"),
+        CallingConvention::Userpurge => Some("__userpurge"),
+        CallingConvention::Invalid | CallingConvention::Unknown | CallingConvention::Manual => None,
     }
 }
 
-enum DecompressionError {
-    Error(TINFLStatus),
+/// The braced constant list of an enum, unnamed constants are numbered.
+fn c_enum_body(enum_type: &Enum, info: Option<&TILTypeInfo>) -> String {
+    let mut body = "{\n".to_string();
+    for (index, member) in enum_type.members.iter().enumerate() {
+        let name = match info.and_then(|info| info.fields.0.get(index)) {
+            Some(name) => name.clone(),
+            None => format!("member_{}", index),
+        };
+        body += &format!(
+            "  {} = {},{}\n",
+            name,
+            enum_type.format_member(member),
+            c_field_comment(info, index)
+        );
+    }
+    body + "}"
 }
 
-impl Debug for DecompressionError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Decompression Error: {}",
-            match self {
-                DecompressionError::Error(status) => *status as u8,
-                _ => 0,
-            }
-        )
+/// A member's comment as a trailing ` // ...`, or nothing.
+fn c_field_comment(info: Option<&TILTypeInfo>, index: usize) -> String {
+    match info.and_then(|info| info.field_comment(index)) {
+        Some(comment) => format!(" // {}", comment.replace('\n', " ")),
+        None => String::new(),
     }
 }
 
-impl Display for DecompressionError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Decompression Error: {}",
-            match self {
-                DecompressionError::Error(status) => *status as u8,
-                _ => 0,
+/// How deep types may nest when sizing them, which also stops typedef cycles.
+const MAX_TYPE_DEPTH: u32 = 32;
+
+/// A struct member's offset and size in bytes.
+type MemberOffset = (u64, u64);
+
+fn round_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+/// Resolves ordinals across a TIL and the base TILs it depends on.
+pub struct TILResolver {
+    pub section: TILSection,
+    bases: Vec<(String, TILSection)>,
+}
+
+impl TILResolver {
+    pub fn new(section: TILSection) -> Self {
+        Self {
+            section,
+            bases: Vec::new(),
+        }
+    }
+
+    /// The base TILs the section declares, in declaration order.
+    pub fn base_names(&self) -> Vec<&str> {
+        self.section
+            .base
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    /// Parses and registers a standalone base TIL under `name`, which should
+    /// be one of [`TILResolver::base_names`].
+    pub fn load_base(&mut self, name: &str, bytes: &[u8]) -> BinResult<()> {
+        let base = TILSection::parse(bytes)?;
+        self.bases.retain(|(loaded, _)| loaded != name);
+        self.bases.push((name.to_string(), base));
+        Ok(())
+    }
+
+    /// Looks the ordinal up in the section first, then in each loaded base in
+    /// declaration order. Bases that aren't loaded are skipped.
+    pub fn resolve_ordinal(&self, ordinal: u64) -> Option<&TILTypeInfo> {
+        self.section.get_type_by_ordinal(ordinal).or_else(|| {
+            self.base_names().into_iter().find_map(|name| {
+                self.bases
+                    .iter()
+                    .find(|(loaded, _)| loaded == name)
+                    .and_then(|(_, base)| base.get_type_by_ordinal(ordinal))
+            })
+        })
+    }
+
+    /// Indexes the section and every loaded base, with the section taking
+    /// precedence and bases following in declaration order.
+    pub fn catalog(&self) -> TypeCatalog<'_> {
+        let mut catalog = self.section.catalog();
+        for name in self.base_names() {
+            if let Some((_, base)) = self.bases.iter().find(|(loaded, _)| loaded == name) {
+                catalog.add(base);
             }
-        )
+        }
+        catalog
     }
 }
 
-impl std::error::Error for DecompressionError {}
+/// Name and ordinal indices over one or more TILs. Names cover both the types
+/// and symbols buckets, ordinals only the types bucket. When an entry appears
+/// more than once the first one added wins.
+#[derive(Debug, Default)]
+pub struct TypeCatalog<'a> {
+    names: HashMap<&'a [u8], &'a TILTypeInfo>,
+    ordinals: HashMap<u64, &'a TILTypeInfo>,
+}
 
-fn stream_len<R: Read + Seek>(reader: &mut R) -> std::io::Result<u64> {
-    let old_pos = reader.stream_position()?;
-    let len = reader.seek(SeekFrom::End(0))?;
+impl<'a> TypeCatalog<'a> {
+    fn add(&mut self, section: &'a TILSection) {
+        for info in section.types.type_info() {
+            self.names.entry(&info.name.0).or_insert(info);
+            self.ordinals.entry(info.ordinal.value()).or_insert(info);
+        }
+        for info in section.symbols.type_info() {
+            self.names.entry(&info.name.0).or_insert(info);
+        }
+    }
 
-    // Avoid seeking a third time when we were already at the end of the
-    // stream. The branch is usually way cheaper than a seek operation.
-    if old_pos != len {
-        reader.seek(SeekFrom::Start(old_pos))?;
+    pub fn resolve(&self, name: &str) -> Option<&'a TILTypeInfo> {
+        self.names.get(name.as_bytes()).copied()
     }
 
-    Ok(len)
+    pub fn resolve_ordinal(&self, ordinal: u64) -> Option<&'a TILTypeInfo> {
+        self.ordinals.get(&ordinal).copied()
+    }
+
+    /// Resolves the entry a typedef refers to, by ordinal or by name. Other
+    /// types don't refer to an entry and return `None`.
+    pub fn resolve_type(&self, typ: &Types) -> Option<&'a TILTypeInfo> {
+        match typ {
+            Types::Typedef(typedef) if typedef.is_ordref => {
+                self.resolve_ordinal(typedef.ordinal.0 as u64)
+            }
+            Types::Typedef(typedef) => self.resolve(&typedef.name),
+            _ => None,
+        }
+    }
 }
 
-impl BinRead for TILBucketZip {
-    type Args = <TILBucket as BinRead>::Args;
+/// The root node altval holding the image base, index -6. Netnode keys
+/// only keep the low `word_size` bytes of it.
+const RIDX_ALT_IMAGEBASE: u64 = -6_i64 as u64;
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        options: &ReadOptions,
-        args: Self::Args,
-    ) -> BinResult<Self> {
-        let ndefs = reader.read_ne()?;
-        let len = reader.read_ne()?;
-        let compressed_len = reader.read_ne::<u32>()?;
+impl IDB {
+    pub fn parse(bytes: &[u8]) -> BinResult<Self> {
+        IdbParser::new().parse(bytes)
+    }
 
-        let restore = reader.stream_position()?;
+    /// Recomputes the checksum of every section of `bytes`, the buffer this
+    /// was parsed from, and returns the first section that doesn't match the
+    /// header.
+    pub fn verify_checksums(&self, bytes: &[u8]) -> Result<(), IDBSectionKind> {
+        for (kind, offset, checksum) in self.header.section_checksums() {
+            if offset == 0 {
+                continue;
+            }
+            match section_at(bytes, offset) {
+                Some((_, body)) if crc32(body) == checksum => {}
+                _ => return Err(kind),
+            }
+        }
+        Ok(())
+    }
 
-        let data_compressed = reader.read_ne_args::<Vec<u8>>(binrw::VecArgs {
-            count: compressed_len as usize,
-            inner: (),
-        })?;
+    /// True for .i64 databases, whose addresses and netnode ids are 64 bit.
+    pub fn is_64(&self) -> bool {
+        self.header.is_64()
+    }
 
-        let data =
-            miniz_oxide::inflate::decompress_to_vec_zlib(&data_compressed).map_err(|err| {
-                binrw::Error::Custom {
-                    pos: restore,
-                    err: Box::new(DecompressionError::Error(err)),
-                }
-            })?;
+    /// The size in bytes of addresses and netnode ids, the `word_size` taken
+    /// by [`Netnode::new`] and [`SEGSection::from_id0`].
+    pub fn word_size(&self) -> u8 {
+        self.header.word_size()
+    }
 
-        let post = reader.stream_position()?;
-        reader.seek(SeekFrom::Start(restore));
-        let mut cursor = binrw::io::Cursor::new(data.as_slice());
-        // println!("START READ...");
-        // let type_info = cursor.read_ne_args(binrw::VecArgs::<(u8,)> {
-        //     count: ndefs as usize,
-        //     inner: (args.size_e,),
-        // })?;
-        // println!("DONE...");
-
-        let type_info = (0..ndefs)
-            .map(|ind| {
-                // println!(
-                //     "{}->{} :: {}",
-                //     cursor.stream_position().unwrap(),
-                //     stream_len(&mut cursor).unwrap(),
-                //     data.len()
-                // );
-                // let POS = cursor.stream_position().unwrap();
-                // let _ = cursor.read_ne::<u32>().unwrap();
-                // let str = cursor.read_ne::<binrw::NullString>().unwrap();
-                // println!("GOINGTOPARSE:{} @ {}", str.clone().into_string(), ind);
-                // cursor.seek(SeekFrom::Start(POS));
-
-                // if str.clone().into_string() == "IN_DECLS" {
-                //     println!("-MARKER");
-                // }
-
-                let ok = cursor.read_ne_args::<TILTypeInfo>((args.size_e,)).unwrap();
-                // if ok.name.clone().into_string() == "-[NSPointerFunctions initWithOptions:]" {
-                //     println!("{:#x?}", ok);
-                // }
-                ok
-            })
-            .collect::<Vec<TILTypeInfo>>();
+    /// The base address IDA loaded the input file at, from the root node,
+    /// for translating addresses back to the file. `None` without id0.
+    pub fn image_base(&self) -> Option<u64> {
+        let netnode = Netnode::new(self.id0.as_ref()?, self.word_size())?;
+        let root = netnode.node_by_name(b"Root Node")?;
+        netnode.altval(root, RIDX_ALT_IMAGEBASE)
+    }
 
-        reader.seek(SeekFrom::Start(post));
+    /// The segment whose `start..end` range holds `ea`, `None` for addresses
+    /// between segments or when there is no segment table.
+    pub fn segment_containing(&self, ea: u64) -> Option<&Segment> {
+        self.seg.as_ref()?.segment_at(ea)
+    }
 
-        Ok(Self {
-            ndefs,
-            len,
-            compressed_len,
-            type_info,
-            data,
-        })
+    /// Parses from a reader positioned at the start of the database, see
+    /// [`IdbParser::parse_from_reader`].
+    pub fn parse_from_reader<R: Read + Seek>(reader: R) -> BinResult<Self> {
+        IdbParser::new().parse_from_reader(reader)
+    }
+
+    pub fn parse_from_file(path: String) -> BinResult<Self> {
+        let file = File::open(path)?;
+        IDB::parse_from_reader(std::io::BufReader::new(file))
+    }
+
+    /// Parses a memory mapped database instead of reading it through a
+    /// buffer. Every section is copied out of the mapping while parsing, so
+    /// the mapping is unmapped before this returns and nothing borrows it.
+    ///
+    /// The file must not be modified while it is being parsed.
+    #[cfg(feature = "mmap")]
+    pub fn parse_from_mmap(path: &Path) -> BinResult<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read only and dropped before returning, the
+        // caller guarantees the file isn't truncated or written meanwhile.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        IDB::parse(&mmap)
     }
 }
 
+/// An [`IDB`] over a borrowed buffer that only reads the header up front.
+/// Each section is parsed the first time its accessor is called and cached,
+/// so asking for the TIL never reads the other sections. Accessors return
+/// `Ok(None)` for sections the database doesn't have.
 #[derive(Debug)]
-// #[binread]
-// #[br(import { is_zip: bool })]
-pub enum TILBucketType {
-    // #[br(pre_assert(is_zip == false))]
-    Default(TILBucket),
-    // #[br(pre_assert(is_zip == true))]
-    Zip(TILBucketZip),
+pub struct LazyIDB<'a> {
+    bytes: &'a [u8],
+    header: IDBHeader,
+    id0: OnceCell<Option<ID0Section>>,
+    id1: OnceCell<Option<ID1Section>>,
+    seg: OnceCell<Option<SEGSection>>,
+    til: OnceCell<Option<TILSection>>,
+    id2: OnceCell<Option<ID2Section>>,
 }
 
-impl BinRead for TILBucketType {
-    type Args = (bool, u8);
+impl<'a> LazyIDB<'a> {
+    /// Reads the header of `bytes`. With `eager` every section is parsed
+    /// straight away as [`IDB::parse`] would, failing on the first bad one.
+    pub fn new(bytes: &'a [u8], eager: bool) -> BinResult<Self> {
+        trace_span!("lazy_idb", len = bytes.len(), eager);
+        let header = binrw::io::Cursor::new(bytes).read_ne()?;
+        let idb = Self {
+            bytes,
+            header,
+            id0: OnceCell::new(),
+            id1: OnceCell::new(),
+            seg: OnceCell::new(),
+            til: OnceCell::new(),
+            id2: OnceCell::new(),
+        };
+        if eager {
+            idb.id0()?;
+            idb.id1()?;
+            idb.seg()?;
+            idb.til()?;
+            idb.id2()?;
+        }
+        Ok(idb)
+    }
 
-    fn read_options<R: Read + Seek>(
-        reader: &mut R,
-        options: &ReadOptions,
-        args: Self::Args,
-    ) -> BinResult<Self> {
-        if args.0 == false {
-            Ok(Self::Default(
-                reader.read_ne_args(TILBucketBinReadArgs { size_e: args.1 })?,
-            ))
-        } else {
-            Ok(Self::Zip(
-                reader.read_ne_args(TILBucketBinReadArgs { size_e: args.1 })?,
-            ))
+    /// Returns the cached section, parsing it at `offset` on first use.
+    /// Errors aren't cached, a later call tries again.
+    fn section<'s, T: BinRead>(
+        &self,
+        cell: &'s OnceCell<Option<T>>,
+        offset: u64,
+        args: T::Args,
+    ) -> BinResult<Option<&'s T>> {
+        self.section_with(cell, offset, |cursor| cursor.read_ne_args(args).map(Some))
+    }
+
+    /// Like [`LazyIDB::section`], reading the section at `offset` with `read`.
+    fn section_with<'s, T>(
+        &self,
+        cell: &'s OnceCell<Option<T>>,
+        offset: u64,
+        read: impl FnOnce(&mut binrw::io::Cursor<&'a [u8]>) -> BinResult<Option<T>>,
+    ) -> BinResult<Option<&'s T>> {
+        if let Some(section) = cell.get() {
+            return Ok(section.as_ref());
         }
+        let section = if offset == 0 {
+            None
+        } else {
+            let mut cursor = binrw::io::Cursor::new(self.bytes);
+            cursor.seek(SeekFrom::Start(offset))?;
+            read(&mut cursor)?
+        };
+        Ok(cell.get_or_init(|| section).as_ref())
     }
-}
 
-#[binread]
-#[derive(Debug)]
-#[br(import(is_standalone: bool))]
-pub struct TILSection {
-    #[br(if(is_standalone == false))]
-    header: IDBSectionHeader,
-    #[br(
-    count = 6,
-    map = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned(),
-    assert(signature == "IDATIL"))]
-    signature: String,
-    format: u32,
-    flags: u32,
-    #[br(temp)]
-    title_len: u8,
-    #[br(
-    count = title_len,
-    map = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned())]
-    title: String,
-    #[br(temp)]
-    base_len: u8,
-    #[br(
-    count = base_len,
-    map = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned())]
-    base: String,
-    id: u8,
-    cm: u8,
-    size_i: u8,
-    size_b: u8,
-    size_e: u8,
-    def_align: u8,
-    #[br(if((flags & TIL_ESI) > 0))]
-    size_s: Option<u8>,
-    #[br(if((flags & TIL_ESI) > 0))]
-    size_l: Option<u8>,
-    #[br(if((flags & TIL_ESI) > 0))]
-    size_ll: Option<u8>,
-    #[br(if((flags & TIL_SLD) > 0))]
-    size_ldbl: Option<u8>,
-    #[br(args((flags & TIL_ZIP) > 0, size_e))]
-    pub symbols: TILBucketType,
-    #[br(if((flags & TIL_ORD) > 0))]
-    type_ordinal_numbers: Option<u32>,
-    #[br(args((flags & TIL_ZIP) > 0, size_e))]
-    pub types: TILBucketType,
-    // TODO: Fix this, I think the structures differ from the other buckets.
-    // #[br(args((flags & TIL_ZIP) > 0, size_e))]
-    // macros: TILBucketType,
-}
+    pub fn id0(&self) -> BinResult<Option<&ID0Section>> {
+        self.section(&self.id0, self.header.id0_offset, ())
+    }
 
-#[derive(BinRead, Debug)]
-struct ID2Section {}
+    pub fn id1(&self) -> BinResult<Option<&ID1Section>> {
+        self.section(&self.id1, self.header.id1_offset, (self.header.is_64(),))
+    }
 
-#[derive(BinRead, Debug)]
-pub struct IDB {
-    header: IDBHeader,
-    #[br(seek_before = SeekFrom::Start(header.id0_offset), if(header.id0_offset != 0))]
-    id0: Option<ID0Section>,
-    #[br(seek_before = SeekFrom::Start(header.id1_offset), if(header.id1_offset != 0))]
-    id1: Option<ID1Section>,
-    #[br(seek_before = SeekFrom::Start(header.nam_offset), if(header.nam_offset != 0))]
-    nam: Option<NAMSection>,
-    #[br(seek_before = SeekFrom::Start(header.seg_offset), if(header.seg_offset != 0))]
-    seg: Option<SEGSection>,
-    #[br(seek_before = SeekFrom::Start(header.til_offset), if(header.til_offset != 0))]
-    pub til: Option<TILSection>,
-    #[br(seek_before = SeekFrom::Start(header.id2_offset), if(header.id2_offset != 0))]
-    id2: Option<ID2Section>,
-}
+    /// Read from the ID0 section like [`IDB::seg`], parsing that first.
+    pub fn seg(&self) -> BinResult<Option<&SEGSection>> {
+        if let Some(seg) = self.seg.get() {
+            return Ok(seg.as_ref());
+        }
+        let seg = self
+            .id0()?
+            .and_then(|id0| SEGSection::from_id0(id0, self.header.word_size()));
+        Ok(self.seg.get_or_init(|| seg).as_ref())
+    }
 
-impl TILSection {
-    pub fn parse(bytes: &[u8]) -> BinResult<Self> {
-        let mut cursor = binrw::io::Cursor::new(bytes);
-        Ok(cursor.read_ne_args((true,))?)
+    pub fn til(&self) -> BinResult<Option<&TILSection>> {
+        self.section_with(&self.til, self.header.til_offset, |cursor| {
+            read_til_section(
+                cursor,
+                &ReadOptions::default(),
+                (TypeDepth::default(), false),
+            )
+        })
     }
 
-    pub fn parse_from_file(path: String) -> BinResult<Self> {
-        let file = File::open(path)?;
-        let mut reader = std::io::BufReader::new(file);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer)?;
-        TILSection::parse(&buffer)
+    pub fn id2(&self) -> BinResult<Option<&ID2Section>> {
+        self.section(&self.id2, self.header.id2_offset, ())
     }
-}
 
-impl IDB {
-    pub fn parse(bytes: &[u8]) -> BinResult<Self> {
-        let mut cursor = binrw::io::Cursor::new(bytes);
-        Ok(cursor.read_ne()?)
+    /// See [`IDB::is_64`].
+    pub fn is_64(&self) -> bool {
+        self.header.is_64()
     }
 
-    pub fn parse_from_file(path: String) -> BinResult<Self> {
-        let file = File::open(path)?;
-        let mut reader = std::io::BufReader::new(file);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer)?;
-        IDB::parse(&buffer)
+    /// The header of a section, `None` when the database doesn't have it or
+    /// it doesn't fit in the buffer.
+    pub fn section_header(&self, kind: IDBSectionKind) -> Option<IDBSectionHeader> {
+        self.section_at(kind).map(|(header, _)| header)
+    }
+
+    /// The body of a section as stored, after its header and without
+    /// decompressing it, for sections the typed accessors can't read.
+    pub fn raw_section(&self, kind: IDBSectionKind) -> Option<&'a [u8]> {
+        self.section_at(kind).map(|(_, body)| body)
+    }
+
+    fn section_at(&self, kind: IDBSectionKind) -> Option<(IDBSectionHeader, &'a [u8])> {
+        match self.header.section_offset(kind) {
+            0 => None,
+            offset => section_at(self.bytes, offset),
+        }
     }
 }
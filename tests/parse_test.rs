@@ -1,5 +1,6 @@
 use idb_parser;
 use idb_parser::TILBucketType;
+use idb_parser::TypeDepth;
 use std::borrow::Borrow;
 
 const IDB: &'static [u8] = include_bytes!("resources/gcc.i64");
@@ -8,6 +9,34 @@ const IDB: &'static [u8] = include_bytes!("resources/gcc.i64");
 // and can be parsed using this library
 const TIL: &'static [u8] = include_bytes!("resources/gcc.til");
 
+/// The types bucket of `til`, which gcc.til stores uncompressed.
+fn types_bucket(til: &idb_parser::TILSection) -> &idb_parser::TILBucket {
+    match &til.types {
+        TILBucketType::Default(def) => def,
+        TILBucketType::Zip(_) => unreachable!(),
+    }
+}
+
+/// The raw data of the types bucket of `til`.
+fn types_data(til: &idb_parser::TILSection) -> Vec<u8> {
+    let repacked = til.to_bytes().unwrap();
+    repacked[repacked.len() - types_bucket(til).data_len()..].to_vec()
+}
+
+/// gcc.til with `count` encoded type entries appended to its types bucket.
+fn til_with_types(entries: &[u8], count: u32) -> Vec<u8> {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let types = types_bucket(&til);
+    let repacked = til.to_bytes().unwrap();
+    let bucket_start = repacked.len() - types.data_len() - 8;
+    let mut bytes = repacked[..bucket_start].to_vec();
+    bytes.extend_from_slice(&(types.ndefs + count).to_le_bytes());
+    bytes.extend_from_slice(&((types.data_len() + entries.len()) as u32).to_le_bytes());
+    bytes.extend_from_slice(&repacked[bucket_start + 8..]);
+    bytes.extend_from_slice(entries);
+    bytes
+}
+
 #[test]
 fn test_parse_idb() {
     let _idb = idb_parser::IDB::parse(IDB).unwrap();
@@ -23,14 +52,2366 @@ fn test_idb_til_same() {
     let idb = idb_parser::IDB::parse(IDB).unwrap();
     let til = idb_parser::TILSection::parse(TIL).unwrap();
 
-    let idb_type_ndefs = match idb.til.unwrap().types {
-        TILBucketType::Default(def) => def.ndefs as usize + def.type_info.len(),
-        TILBucketType::Zip(zip) => zip.ndefs as usize + zip.type_info.len(),
+    let idb_til = idb.til.unwrap();
+    let types = |til: &idb_parser::TILSection| {
+        til.iter_types()
+            .map(|info| info.name_str().into_owned())
+            .collect::<Vec<_>>()
+    };
+    let symbols = |til: &idb_parser::TILSection| {
+        til.iter_symbols()
+            .map(|info| info.name_str().into_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert!(!types(&til).is_empty());
+    assert_eq!(types(&idb_til), types(&til));
+    assert_eq!(symbols(&idb_til), symbols(&til));
+}
+
+#[test]
+fn test_null_vec_len_string_eof() {
+    use binrw::BinReaderExt;
+
+    // No trailing null byte, the last entry also claims more bytes than remain.
+    let mut cursor = binrw::io::Cursor::new(vec![3, b'a', b'b', 4, b'c']);
+    let strings = cursor.read_ne::<idb_parser::NullVecLenString>().unwrap();
+    assert_eq!(strings.0, vec!["ab".to_string(), "c".to_string()]);
+}
+
+#[test]
+fn test_til_to_bytes_round_trip() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let bytes = til.to_bytes().unwrap();
+    let repacked = idb_parser::TILSection::parse(&bytes).unwrap();
+
+    let names = |entries: &mut dyn Iterator<Item = &idb_parser::TILTypeInfo>| {
+        entries
+            .map(|info| info.name_str().into_owned())
+            .collect::<Vec<_>>()
+    };
+    assert!(!names(&mut til.iter_types()).is_empty());
+    assert_eq!(
+        names(&mut til.iter_types()),
+        names(&mut repacked.iter_types())
+    );
+    assert_eq!(
+        names(&mut til.iter_symbols()),
+        names(&mut repacked.iter_symbols())
+    );
+    assert_eq!(bytes, repacked.to_bytes().unwrap());
+
+    // Field names are written behind a length byte counting the name plus one
+    let set_field = |til: &mut idb_parser::TILSection, len: usize| match &mut til.types {
+        TILBucketType::Default(def) => def.type_info[0].fields.0 = vec!["x".repeat(len)],
+        TILBucketType::Zip(zip) => zip.type_info[0].fields.0 = vec!["x".repeat(len)],
+    };
+    let mut til = til;
+    set_field(&mut til, 254);
+    assert!(til.to_bytes().is_ok());
+    set_field(&mut til, 255);
+    let err = til.to_bytes().unwrap_err();
+    assert!(err.to_string().contains("length 256 doesn't fit"));
+}
+
+#[test]
+fn test_til_parse_stats() {
+    let (til, stats) = idb_parser::TILSection::parse_with_stats(TIL).unwrap();
+    assert_eq!(stats.types.entries, til.iter_types().count());
+    assert!(stats.types.parse_time > std::time::Duration::ZERO);
+    assert!(stats.symbols.parse_time > std::time::Duration::ZERO);
+}
+
+#[test]
+fn test_til_extended_sizes() {
+    // Patch TIL_ESI | TIL_SLD into gcc.til and insert the extra size block
+    // (size_s, size_l, size_ll, size_ldbl) right after def_align.
+    let mut bytes = TIL.to_vec();
+    let flags = u32::from_le_bytes(bytes[10..14].try_into().unwrap()) | 0x0004 | 0x0100;
+    bytes[10..14].copy_from_slice(&flags.to_le_bytes());
+    let def_align = 14 + 1 + bytes[14] as usize;
+    let def_align = def_align + 1 + bytes[def_align] as usize + 5;
+    bytes.splice(def_align + 1..def_align + 1, [2, 8, 8, 16]);
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let extended = idb_parser::TILSection::parse(&bytes).unwrap();
+    let first_name =
+        |til: &idb_parser::TILSection| til.iter_types().next().unwrap().name_str().into_owned();
+    assert_eq!(first_name(&til), first_name(&extended));
+}
+
+#[test]
+fn test_til_flags() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    // gcc.til has flags 0x12, TIL_MAC | TIL_ORD
+    assert!(til.is_mac());
+    assert!(til.has_ordinals());
+    assert!(!til.is_zipped());
+    assert!(!til.is_universal());
+
+    let mut bytes = TIL.to_vec();
+    bytes[10] |= 0x08;
+    assert!(idb_parser::TILSection::parse(&bytes)
+        .unwrap()
+        .is_universal());
+}
+
+#[test]
+fn test_bucket_data_len() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let data_len = types_bucket(&til).data_len();
+
+    assert!(data_len > 0);
+    assert_eq!(types_bucket(&idb.til.unwrap()).data_len(), data_len);
+}
+
+#[test]
+fn test_enum_base_type() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // BT_COMPLEX | BTMT_ENUM, one member, BTE_ALWAYS with a 2 byte size, value 5
+    let mut cursor = binrw::io::Cursor::new(vec![0x2D, 0x02, 0x82, 0x05]);
+    let enum_type = match cursor.read_ne::<Types>().unwrap() {
+        Types::Enum(enum_type) => enum_type,
+        other => panic!("expected an enum, got {:?}", other),
+    };
+    assert_eq!(enum_type.bytesize, 2);
+    match enum_type.base_type() {
+        Types::Unset(metadata) => {
+            assert_eq!(metadata.get_base_type_flag().0, 0x03);
+            assert!(metadata.get_type_flag().is_unsigned());
+        }
+        other => panic!("expected a primitive, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_enum_member_values() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let members = |bytes: Vec<u8>| {
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        match cursor.read_ne::<Types>().unwrap() {
+            Types::Enum(enum_type) => enum_type
+                .members
+                .iter()
+                .map(|member| member.0)
+                .collect::<Vec<_>>(),
+            other => panic!("expected an enum, got {:?}", other),
+        }
+    };
+
+    // Two members of an 8 byte enum with TAENUM_64BIT, each a low and a high
+    // word delta: 0x1_00000001, then 0x3F_0000003F more
+    let wide = vec![0x2D, 0x03, 0xFE, 0x20, 0x84, 0x01, 0x01, 0x3F, 0x3F];
+    assert_eq!(members(wide), vec![0x1_0000_0001, 0x40_0000_0040]);
+
+    // A 4 byte enum counting down by adding 0xFFFFFFFF wraps at 32 bits
+    let narrow = vec![0x2D, 0x03, 0x83, 0x05, 0x9F, 0xFF, 0xFF, 0xFF, 0x3F];
+    assert_eq!(members(narrow), vec![5, 4]);
+}
+
+#[test]
+fn test_enum_groups() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // A 4 byte bitmask enum: the flag 0x1 on its own, then the mask 0x6 with
+    // the values 0x2 and 0x4. Group sizes precede each group's first member.
+    let bytes = vec![
+        0x2D, 0x05, 0x93, // 4 members, BTE_ALWAYS | BTE_BITFIELD, 4 bytes
+        0x02, 0x01, // group of 1, +1
+        0x04, 0x05, // group of 3, +5
+        0x9F, 0xFF, 0xFF, 0xFF, 0x3C, // -4
+        0x02, // +2
+    ];
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let enum_type = match cursor.read_ne::<Types>().unwrap() {
+        Types::Enum(enum_type) => enum_type,
+        other => panic!("expected an enum, got {:?}", other),
+    };
+    assert_eq!(cursor.position(), 13);
+    let groups = enum_type
+        .groups()
+        .into_iter()
+        .map(|(mask, members)| (mask, members.iter().map(|member| member.0).collect()))
+        .collect::<Vec<(u64, Vec<u64>)>>();
+    assert_eq!(groups, vec![(0x1, vec![0x1]), (0x6, vec![0x6, 0x2, 0x4])]);
+
+    let mut cursor = binrw::io::Cursor::new(vec![0x2D, 0x02, 0x82, 0x05]);
+    match cursor.read_ne::<Types>().unwrap() {
+        Types::Enum(enum_type) => assert!(enum_type.groups().is_empty()),
+        other => panic!("expected an enum, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_enum_representation() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let parse = |bytes: Vec<u8>| {
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        let typ = cursor.read_ne::<Types>().unwrap();
+        let (radix, signed, char) = match &typ {
+            Types::Enum(enum_type) => (
+                enum_type.radix(),
+                enum_type.is_signed(),
+                enum_type.is_char(),
+            ),
+            other => panic!("expected an enum, got {:?}", other),
+        };
+        (radix, signed, char, til.c_declaration(&typ, "e"))
+    };
+
+    // Hexadecimal, 5 then 0x44
+    assert_eq!(
+        parse(vec![0x2D, 0x03, 0x82, 0x05, 0x3F]),
+        (
+            16,
+            false,
+            false,
+            "enum {\n  member_0 = 0x5,\n  member_1 = 0x44,\n} e".to_string()
+        )
+    );
+    // Signed decimal 4 byte, 5 then -5
+    let (radix, signed, _, declaration) =
+        parse(vec![0x2D, 0x03, 0xC3, 0x05, 0x9F, 0xFF, 0xFF, 0xFF, 0x36]);
+    assert_eq!((radix, signed), (10, true));
+    assert!(declaration.contains("  member_1 = -5,\n"));
+    // Unsigned decimal, the same values
+    let (radix, signed, _, declaration) =
+        parse(vec![0x2D, 0x03, 0xE3, 0x05, 0x9F, 0xFF, 0xFF, 0xFF, 0x36]);
+    assert_eq!((radix, signed), (10, false));
+    assert!(declaration.contains("  member_1 = 4294967291,\n"));
+    // Character, 'A'
+    let (_, _, char, declaration) = parse(vec![0x2D, 0x02, 0xA1, 0x81, 0x01]);
+    assert!(char);
+    assert!(declaration.contains("  member_0 = 'A',\n"));
+}
+
+#[test]
+fn test_zip_bucket_short_data() {
+    use binrw::BinReaderExt;
+
+    // A single entry: flags, name "a", ordinal 1, type "int", empty cmt/fields/fieldcmts, sclass
+    let entry = [0, 0, 0, 0, b'a', 0, 1, 0, 0, 0, 0x07, 0, 0, 0, 0, 0];
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&entry, 6);
+    let mut bucket = Vec::new();
+    bucket.extend_from_slice(&2_u32.to_le_bytes());
+    bucket.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+    bucket.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    bucket.extend(compressed);
+
+    let mut cursor = binrw::io::Cursor::new(bucket);
+    let zip = cursor
+        .read_ne_args::<idb_parser::TILBucketZip>((4, false, TypeDepth::default(), false))
+        .unwrap();
+    assert_eq!(zip.type_info.len(), 1);
+    assert_eq!(zip.type_info[0].name.clone().into_string(), "a");
+    assert_eq!(zip.ndefs_mismatch(), Some((2, 1)));
+}
+
+#[test]
+fn test_type_kind() {
+    use idb_parser::TypeKind;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let kind = |name: &str| {
+        til.iter_types()
+            .find(|info| info.name.clone().into_string() == name)
+            .map(|info| info.tinfo.kind())
+            .unwrap()
     };
-    let til_type_ndefs = match til.types {
-        TILBucketType::Default(def) => def.ndefs as usize + def.type_info.len(),
-        TILBucketType::Zip(zip) => zip.ndefs as usize + zip.type_info.len(),
+
+    assert_eq!(kind("uint32_t"), TypeKind::Primitive);
+    assert_eq!(kind("cpu_type_t"), TypeKind::Typedef);
+    assert_eq!(kind("mach_header_64"), TypeKind::Struct);
+    assert_eq!(kind("lc_str"), TypeKind::Union);
+    assert_eq!(kind("PPUNICODE_STRING"), TypeKind::Pointer);
+    assert_eq!(kind("gcc_va_list"), TypeKind::Array);
+    assert!(til
+        .iter_types()
+        .all(|info| info.tinfo.kind() != TypeKind::Unknown));
+}
+
+#[test]
+fn test_empty_buckets() {
+    use binrw::BinReaderExt;
+
+    let mut cursor = binrw::io::Cursor::new([0_u8; 8]);
+    match cursor
+        .read_ne_args::<TILBucketType>((false, 4, false, TypeDepth::default(), false))
+        .unwrap()
+    {
+        TILBucketType::Default(def) => {
+            assert!(def.type_info.is_empty());
+            assert_eq!(def.data_len(), 0);
+        }
+        TILBucketType::Zip(_) => unreachable!(),
+    }
+    assert_eq!(cursor.position(), 8);
+
+    let mut cursor = binrw::io::Cursor::new([0_u8; 12]);
+    match cursor
+        .read_ne_args::<TILBucketType>((true, 4, false, TypeDepth::default(), false))
+        .unwrap()
+    {
+        TILBucketType::Zip(zip) => {
+            assert!(zip.type_info.is_empty());
+            assert_eq!(zip.ndefs_mismatch(), None);
+        }
+        TILBucketType::Default(_) => unreachable!(),
+    }
+    assert_eq!(cursor.position(), 12);
+}
+
+#[test]
+fn test_type_bytes() {
+    use binrw::BinReaderExt;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let info = til
+        .iter_types()
+        .find(|info| info.name.clone().into_string() == "segment_command_64")
+        .unwrap();
+
+    let bytes = til.type_bytes("segment_command_64").unwrap();
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let decoded = cursor.read_ne::<idb_parser::Types>().unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", info.tinfo));
+    assert!(til.type_bytes("does_not_exist").is_none());
+}
+
+#[test]
+fn test_byte_flag_helpers() {
+    // Start of an instruction with a name and a flow reference
+    let code = 0x0001_4655;
+    assert!(idb_parser::is_code(code));
+    assert!(!idb_parser::is_data(code));
+    assert_eq!(idb_parser::data_size(code), None);
+
+    // Start of a string literal, and a dword
+    let string = 0x5000_0441;
+    assert!(idb_parser::is_data(string));
+    assert_eq!(idb_parser::data_size(string), None);
+    assert_eq!(idb_parser::data_size(0x2000_0400), Some(4));
+
+    assert!(idb_parser::is_tail(0x0000_0200));
+    assert!(idb_parser::is_unknown(0x0000_0100));
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_events() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+
+    struct CountingSubscriber(Arc<AtomicUsize>);
+
+    impl tracing::Subscriber for CountingSubscriber {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let events = Arc::new(AtomicUsize::new(0));
+    tracing::subscriber::with_default(CountingSubscriber(events.clone()), || {
+        idb_parser::TILSection::parse(TIL).unwrap();
+    });
+    assert!(events.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn test_resolve_to_primitive() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let find = |name: &str| {
+        &til.iter_types()
+            .find(|info| info.name.clone().into_string() == name)
+            .unwrap()
+            .tinfo
+    };
+
+    // cpu_type_t -> integer_t -> int
+    let int = til.resolve_to_primitive(find("cpu_type_t")).unwrap();
+    assert_eq!(int.0, 0x07);
+    // A single member union unwraps to its member, lc_str -> uint32_t
+    let uint32 = til.resolve_to_primitive(find("lc_str")).unwrap();
+    assert_eq!(uint32.0, 0x27);
+    // X wraps a typedef to IUnknown, which this TIL doesn't define
+    assert!(til.resolve_to_primitive(find("X")).is_none());
+    assert!(til.resolve_to_primitive(find("mach_header_64")).is_none());
+}
+
+#[test]
+fn test_type_info_name_str() {
+    use binrw::BinReaderExt;
+    use std::borrow::Cow;
+
+    let entry = |name: &[u8]| {
+        let mut bytes = vec![0, 0, 0, 0];
+        bytes.extend_from_slice(name);
+        bytes.extend_from_slice(&[0, 1, 0, 0, 0, 0x07, 0, 0, 0, 0, 0]);
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        cursor
+            .read_ne_args::<idb_parser::TILTypeInfo>((4, TypeDepth::default()))
+            .unwrap()
     };
 
-    assert_eq!(idb_type_ndefs, til_type_ndefs);
+    let valid = entry(b"size_t");
+    assert!(matches!(valid.name_str(), Cow::Borrowed("size_t")));
+    let invalid = entry(b"bad\xFFname");
+    assert!(matches!(invalid.name_str(), Cow::Owned(_)));
+    assert_eq!(invalid.name_str(), "bad\u{FFFD}name");
+}
+
+#[test]
+fn test_kind_histogram() {
+    use idb_parser::TypeKind;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let histogram = til.kind_histogram();
+
+    assert_eq!(
+        histogram.values().sum::<usize>(),
+        til.iter_types().count() + til.iter_symbols().count()
+    );
+    assert_eq!(histogram[&TypeKind::Union], 1);
+    assert!(histogram[&TypeKind::Struct] > histogram[&TypeKind::Typedef]);
+}
+
+#[test]
+fn test_decode_netnode_key() {
+    use idb_parser::decode_netnode_key;
+
+    let mut key = vec![b'.'];
+    key.extend_from_slice(&0xFF00_0000_0000_0002_u64.to_be_bytes());
+    key.push(b'S');
+    assert_eq!(
+        decode_netnode_key(&key, 8),
+        Some((0xFF00_0000_0000_0002, b'S', None))
+    );
+
+    key.extend_from_slice(&0x1000_u64.to_be_bytes());
+    assert_eq!(
+        decode_netnode_key(&key, 8),
+        Some((0xFF00_0000_0000_0002, b'S', Some(0x1000)))
+    );
+
+    let key = [b'.', 0xFF, 0, 0, 1, b'A', 0, 0, 0, 7];
+    assert_eq!(
+        decode_netnode_key(&key, 4),
+        Some((0xFF00_0001, b'A', Some(7)))
+    );
+    assert_eq!(decode_netnode_key(b"$ segs", 8), None);
+}
+
+#[test]
+fn test_til_resolver_bases() {
+    let mut base = idb_parser::TILSection::parse(TIL).unwrap();
+    match &mut base.types {
+        TILBucketType::Default(def) => def.type_info[0].ordinal = idb_parser::TILOrdinal::U32(100),
+        TILBucketType::Zip(zip) => zip.type_info[0].ordinal = idb_parser::TILOrdinal::U32(100),
+    }
+    let base_bytes = base.to_bytes().unwrap();
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let mut resolver = idb_parser::TILResolver::new(til);
+    assert_eq!(
+        resolver.base_names(),
+        vec!["macosx64_sdk14", "ntddk64_win10"]
+    );
+    assert_eq!(resolver.resolve_ordinal(4).unwrap().name_str(), "integer_t");
+    assert!(resolver.resolve_ordinal(100).is_none());
+
+    resolver.load_base("ntddk64_win10", &base_bytes).unwrap();
+    assert_eq!(
+        resolver.resolve_ordinal(100).unwrap().name_str(),
+        "uint32_t"
+    );
+}
+
+#[test]
+fn test_idb_parser_builder() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let built = idb_parser::IdbParser::new().parse(IDB).unwrap();
+    assert_eq!(format!("{:?}", idb), format!("{:?}", built));
+
+    let without_til = idb_parser::IdbParser::new().skip_til().parse(IDB).unwrap();
+    assert!(without_til.til.is_none());
+}
+
+#[test]
+fn test_type_recursion_limit() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // `pointers` pointers to int, one level each and one for the int
+    let read = |pointers: usize, depth: TypeDepth| {
+        let mut bytes = vec![0x0A; pointers];
+        bytes.push(0x07);
+        binrw::io::Cursor::new(bytes).read_ne_args::<Types>((0, depth))
+    };
+    assert!(read(63, TypeDepth::default()).is_ok());
+    let err = read(64, TypeDepth::default()).unwrap_err();
+    assert!(err.to_string().contains("deeper than 64 levels"));
+    // Fails at the limit instead of overflowing the stack
+    assert!(read(100_000, TypeDepth::default()).is_err());
+    assert!(read(1, TypeDepth::new(2)).is_ok());
+    assert!(read(2, TypeDepth::new(2)).is_err());
+
+    // Entries past the limit fail the parse like other malformed entries
+    let types = |idb: idb_parser::IDB| idb.til.unwrap().iter_types().count();
+    let parser = idb_parser::IdbParser::new();
+    let all = types(parser.parse(IDB).unwrap());
+    assert_eq!(types(parser.max_recursion(1000).parse(IDB).unwrap()), all);
+    let err = parser.max_recursion(1).parse(IDB).unwrap_err();
+    assert!(err.to_string().contains("deeper than 1 levels"));
+}
+
+#[test]
+fn test_function_is_variadic() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let parse = |cc: u8| {
+        // int f(char *, ...) with the given calling convention byte
+        let mut cursor = binrw::io::Cursor::new(vec![0x0C, cc, 0x07, 0x02, 0x0A, 0x32]);
+        match cursor.read_ne::<Types>().unwrap() {
+            Types::Function(function) => function,
+            other => panic!("expected a function, got {:?}", other),
+        }
+    };
+
+    let printf = parse(0x40);
+    assert!(printf.is_variadic());
+    assert_eq!(printf.args.len(), 1);
+    assert!(!parse(0x30).is_variadic());
+
+    // The ellipsis is kept when printing the prototype
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let declaration = |bytes: Vec<u8>| {
+        let typ = binrw::io::Cursor::new(bytes).read_ne::<Types>().unwrap();
+        til.c_declaration(&typ, "f")
+    };
+    assert_eq!(
+        declaration(vec![0x0C, 0x40, 0x07, 0x02, 0x0A, 0x72]),
+        "int __cdecl f(const char *a1, ...)"
+    );
+    assert_eq!(
+        declaration(vec![0x0C, 0x40, 0x07, 0x01]),
+        "int __cdecl f(...)"
+    );
+    assert_eq!(declaration(vec![0x0C, 0x20, 0x07]), "int __cdecl f(void)");
+    assert_eq!(
+        declaration(vec![0x0C, 0x50, 0x01, 0x02, 0x07]),
+        "void __stdcall f(int a1)"
+    );
+}
+
+#[test]
+fn test_c_function_argument_names() {
+    // Append int __cdecl foo(char *a, int b), its argument names as fields
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let mut entry = 0_u32.to_le_bytes().to_vec();
+    entry.extend_from_slice(b"foo\0");
+    entry.extend_from_slice(&(types_bucket(&til).ndefs + 1).to_le_bytes());
+    entry.extend_from_slice(&[0x0C, 0x30, 0x07, 0x03, 0x0A, 0x32, 0x07, 0x00]);
+    entry.push(0);
+    entry.extend_from_slice(b"\x02a\x02b\0");
+    entry.extend_from_slice(&[0, 0]);
+
+    let til = idb_parser::TILSection::parse(&til_with_types(&entry, 1)).unwrap();
+    let header = til.to_c_header();
+    assert!(header.contains("typedef int __cdecl foo(char *a, int b);\n"));
+
+    // Without the entry's fields the arguments are numbered
+    let foo = til
+        .iter_types()
+        .find(|info| info.name_str() == "foo")
+        .unwrap();
+    assert_eq!(
+        til.c_declaration(&foo.tinfo, "foo"),
+        "int __cdecl foo(char *a1, int a2)"
+    );
+}
+
+#[test]
+fn test_function_memory_model() {
+    use binrw::BinReaderExt;
+    use idb_parser::{CallingConvention, MemoryModel, Types};
+
+    let parse = |cc: u8| {
+        // int f(int) with the given calling convention byte
+        let mut cursor = binrw::io::Cursor::new(vec![0x0C, cc, 0x07, 0x02, 0x07]);
+        match cursor.read_ne::<Types>().unwrap() {
+            Types::Function(function) => function,
+            other => panic!("expected a function, got {:?}", other),
+        }
+    };
+
+    let cdecl = parse(0x33);
+    assert_eq!(cdecl.calling_convention(), CallingConvention::Cdecl);
+    assert_eq!(cdecl.memory_model(), MemoryModel::Small);
+    assert!(!cdecl.is_variadic());
+
+    let stdcall = parse(0x57);
+    assert_eq!(stdcall.calling_convention(), CallingConvention::Stdcall);
+    assert_eq!(stdcall.memory_model(), MemoryModel::Large);
+    assert!(!stdcall.is_variadic());
+
+    let ellipsis = parse(0x4B);
+    assert_eq!(ellipsis.calling_convention(), CallingConvention::Cdecl);
+    assert_eq!(ellipsis.memory_model(), MemoryModel::Compact);
+    assert!(ellipsis.is_variadic());
+    assert_eq!(ellipsis.args.len(), 1);
+
+    assert_eq!(parse(0x7C).memory_model(), MemoryModel::Medium);
+}
+
+#[test]
+fn test_zip_bucket_compressed_data() {
+    use binrw::BinReaderExt;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let types = types_bucket(&til);
+    // Re-pack the uncompressed types bucket of gcc.til as a zipped bucket
+    let data = types_data(&til);
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&data, 9);
+    let mut bucket = Vec::new();
+    bucket.extend_from_slice(&types.ndefs.to_le_bytes());
+    bucket.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bucket.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    bucket.extend_from_slice(&compressed);
+
+    let read = |keep_compressed: bool| {
+        binrw::io::Cursor::new(&bucket)
+            .read_ne_args::<idb_parser::TILBucketZip>((
+                4,
+                false,
+                TypeDepth::default(),
+                keep_compressed,
+            ))
+            .unwrap()
+    };
+    let zip = read(true);
+    assert_eq!(zip.compressed_data(), Some(compressed.as_slice()));
+    assert_eq!(zip.to_bytes(), bucket);
+    assert_eq!(zip.type_info.len(), types.type_info.len());
+
+    // Not kept by default, the bucket is compressed again instead
+    let zip = read(false);
+    assert_eq!(zip.compressed_data(), None);
+    let reemitted = zip.to_bytes();
+    assert_eq!(reemitted[..8], bucket[..8]);
+    let compressed_len = u32::from_le_bytes(reemitted[8..12].try_into().unwrap());
+    assert_eq!(compressed_len as usize, reemitted.len() - 12);
+    let inflated = miniz_oxide::inflate::decompress_to_vec_zlib(&reemitted[12..]).unwrap();
+    assert_eq!(inflated, data);
+
+    let unzipped = zip.unzip();
+    assert_eq!(unzipped.ndefs, zip.ndefs);
+    assert_eq!(unzipped.data_len(), data.len());
+    assert_eq!(unzipped.type_info.len(), types.type_info.len());
+    assert!(unzipped
+        .type_info
+        .iter()
+        .zip(&types.type_info)
+        .all(|(unzipped, original)| unzipped.name_str() == original.name_str()));
+}
+
+#[test]
+fn test_large_zip_bucket() {
+    use binrw::BinReaderExt;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let types = types_bucket(&til);
+    let data = types_data(&til).repeat(100);
+    // Stored blocks keep the zlib stream as large as the data, several read chunks
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&data, 0);
+    assert!(compressed.len() > 0x20000);
+    let names = types
+        .type_info
+        .iter()
+        .map(|info| info.name_str())
+        .cycle()
+        .take(types.type_info.len() * 100)
+        .collect::<Vec<_>>();
+
+    // Both the declared length and one far too small to size the buffer by
+    for len in [data.len() as u32, 1] {
+        let mut bucket = Vec::new();
+        bucket.extend_from_slice(&(types.ndefs * 100).to_le_bytes());
+        bucket.extend_from_slice(&len.to_le_bytes());
+        bucket.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        bucket.extend_from_slice(&compressed);
+        bucket.extend_from_slice(b"next");
+
+        let mut cursor = binrw::io::Cursor::new(&bucket);
+        let zip = cursor
+            .read_ne_args::<idb_parser::TILBucketZip>((4, false, TypeDepth::default(), true))
+            .unwrap();
+        assert_eq!(zip.data_len(), data.len());
+        assert_eq!(zip.ndefs_mismatch(), None);
+        assert_eq!(
+            zip.type_info
+                .iter()
+                .map(|info| info.name_str())
+                .collect::<Vec<_>>(),
+            names
+        );
+        assert_eq!(zip.compressed_data(), Some(compressed.as_slice()));
+        assert_eq!(cursor.read_ne::<[u8; 4]>().unwrap(), *b"next");
+    }
+
+    // A stream cut short fails instead of looping for more input
+    let mut bucket = Vec::new();
+    bucket.extend_from_slice(&types.ndefs.to_le_bytes());
+    bucket.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bucket.extend_from_slice(&0x100_u32.to_le_bytes());
+    bucket.extend_from_slice(&compressed[..0x100]);
+    let mut cursor = binrw::io::Cursor::new(&bucket);
+    assert!(cursor
+        .read_ne_args::<idb_parser::TILBucketZip>((4, false, TypeDepth::default(), false))
+        .is_err());
+
+    // Sizes claiming gigabytes in a 14 byte bucket fail without reserving them
+    let mut bucket = vec![1, 0, 0, 0];
+    bucket.extend_from_slice(&u32::MAX.to_le_bytes());
+    bucket.extend_from_slice(&u32::MAX.to_le_bytes());
+    bucket.extend_from_slice(&[0x78, 0x9c]);
+    for keep_compressed in [false, true] {
+        let mut cursor = binrw::io::Cursor::new(&bucket);
+        assert!(cursor
+            .read_ne_args::<idb_parser::TILBucketZip>((
+                4,
+                false,
+                TypeDepth::default(),
+                keep_compressed
+            ))
+            .is_err());
+    }
+}
+
+#[test]
+fn test_decompression_error_message() {
+    use binrw::BinReaderExt;
+    use idb_parser::DecompressionError;
+    use miniz_oxide::inflate::TINFLStatus;
+
+    let zip_bucket = |compressed: &[u8]| {
+        let mut bucket = vec![1, 0, 0, 0, 0x10, 0, 0, 0];
+        bucket.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        bucket.extend_from_slice(compressed);
+        let mut cursor = binrw::io::Cursor::new(bucket);
+        cursor
+            .read_ne_args::<idb_parser::TILBucketZip>((4, false, TypeDepth::default(), false))
+            .unwrap_err()
+    };
+
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&[0x55; 0x10], 9);
+    let err = zip_bucket(&compressed[..4]);
+    let decompression = err.root_cause().custom_err::<DecompressionError>().unwrap();
+    assert_eq!(
+        decompression.status(),
+        TINFLStatus::FailedCannotMakeProgress
+    );
+    assert_eq!(
+        err.root_cause().to_string(),
+        "Decompression Error: unexpected end of input after 0x4 compressed bytes at 0xc"
+    );
+
+    let err = zip_bucket(&[0xFF; 8]);
+    let decompression = err.root_cause().custom_err::<DecompressionError>().unwrap();
+    assert_eq!(decompression.status(), TINFLStatus::Failed);
+    assert!(err.to_string().contains("invalid zlib header"));
+}
+
+#[test]
+fn test_de_decoding() {
+    use binrw::BinReaderExt;
+
+    // Reference encoder: 7 bit continuation groups, big endian, then a final 6 bit group
+    fn encode_de(mut value: u32) -> Vec<u8> {
+        let mut bytes = vec![(value & 0x3F) as u8];
+        value >>= 6;
+        while value != 0 {
+            bytes.push(0x80 | (value & 0x7F) as u8);
+            value >>= 7;
+        }
+        bytes.reverse();
+        bytes
+    }
+
+    let corpus = (0..0x4000)
+        .chain((0..32).map(|bit| 1 << bit))
+        .chain((0..32).map(|bit| (1_u64 << bit) as u32 - 1))
+        .chain([0x7FFF_FFFF, 0xDEAD_BEEF, u32::MAX]);
+    for value in corpus {
+        let bytes = encode_de(value);
+        assert!(bytes.len() <= 5);
+        // The decoder ignores bit 6 of the final byte
+        let mut marked = bytes.clone();
+        *marked.last_mut().unwrap() |= 0x40;
+        for bytes in [bytes, marked] {
+            let mut cursor = binrw::io::Cursor::new(bytes);
+            assert_eq!(cursor.read_ne::<idb_parser::DE>().unwrap().0, value);
+        }
+    }
+
+    let mut cursor = binrw::io::Cursor::new(vec![0xFF; 6]);
+    assert!(cursor.read_ne::<idb_parser::DE>().is_err());
+}
+
+#[test]
+fn test_string_types() {
+    use binrw::BinReaderExt;
+
+    let mut til = idb_parser::TILSection::parse(TIL).unwrap();
+    // Turn uint32_t into 'typedef char uint32_t[16]'
+    let mut cursor = binrw::io::Cursor::new(vec![0x1B, 0x11, 0x32]);
+    let char_array = cursor.read_ne::<idb_parser::Types>().unwrap();
+    match &mut til.types {
+        TILBucketType::Default(def) => def.type_info[0].tinfo = char_array,
+        TILBucketType::Zip(zip) => zip.type_info[0].tinfo = char_array,
+    }
+
+    let names = til
+        .string_types()
+        .iter()
+        .map(|info| info.name_str().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["uint32_t"]);
+}
+
+#[test]
+fn test_type_parse_error_offset() {
+    use binrw::BinReaderExt;
+
+    // flags, name "a", ordinal 1, then a struct whose member count DT is zero
+    let bytes = [0, 0, 0, 0, b'a', 0, 1, 0, 0, 0, 0x0D, 0x00, 0x00];
+    let read = |bytes: &[u8]| {
+        binrw::io::Cursor::new(bytes)
+            .read_ne_args::<idb_parser::TILTypeInfo>((4, TypeDepth::default()))
+            .unwrap_err()
+    };
+    let err = read(&bytes);
+    let context = err
+        .root_cause()
+        .custom_err::<idb_parser::TypeParseError>()
+        .unwrap();
+    assert_eq!(context.offset, 11);
+    assert_eq!(context.bytes_offset, 10);
+    assert_eq!(context.bytes, vec![0x0D, 0x00, 0x00]);
+
+    // Cut off before the member count, the entry ran out of data rather
+    // than being malformed
+    assert!(read(&bytes[..11]).is_eof());
+}
+
+#[test]
+fn test_section_type_parse_error_offset() {
+    let offset = |err: binrw::Error| {
+        err.root_cause()
+            .custom_err::<idb_parser::TypeParseError>()
+            .map(|context| context.offset)
+    };
+
+    // Zero the name length DT of a typedef in the types bucket
+    let mut til = TIL.to_vec();
+    assert_eq!(til[0x9E], 0x3D);
+    til[0x9F] = 0x00;
+    let err = idb_parser::TILSection::parse(&til).unwrap_err();
+    assert_eq!(offset(err), Some(0x9F));
+
+    // The same type inside the IDB's uncompressed TIL section
+    let til_start = IDB
+        .windows(0x20)
+        .position(|window| window == &TIL[0x80..0xA0])
+        .unwrap()
+        - 0x80;
+    let mut idb = IDB.to_vec();
+    idb[til_start + 0x9F] = 0x00;
+    let err = idb_parser::IDB::parse(&idb).unwrap_err();
+    assert_eq!(offset(err), Some(til_start as u64 + 0x9F));
+}
+
+#[test]
+fn test_type_catalog() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let catalog = til.catalog();
+    assert_eq!(catalog.resolve_ordinal(4).unwrap().name_str(), "integer_t");
+    let cpu_type = catalog.resolve("cpu_type_t").unwrap();
+    assert_eq!(
+        catalog.resolve_type(&cpu_type.tinfo).unwrap().name_str(),
+        "integer_t"
+    );
+    assert!(catalog.resolve("does_not_exist").is_none());
+
+    let mut base = idb_parser::TILSection::parse(TIL).unwrap();
+    match &mut base.types {
+        TILBucketType::Default(def) => def.type_info[0].ordinal = idb_parser::TILOrdinal::U32(100),
+        TILBucketType::Zip(zip) => zip.type_info[0].ordinal = idb_parser::TILOrdinal::U32(100),
+    }
+    let mut resolver = idb_parser::TILResolver::new(til);
+    resolver
+        .load_base("macosx64_sdk14", &base.to_bytes().unwrap())
+        .unwrap();
+    let catalog = resolver.catalog();
+    assert_eq!(catalog.resolve_ordinal(100).unwrap().name_str(), "uint32_t");
+    // The section's own uint32_t shadows the base's
+    assert_eq!(catalog.resolve("uint32_t").unwrap().ordinal.value(), 2);
+}
+
+#[test]
+fn test_get_type_by_ordinal() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    assert_eq!(til.get_type_by_ordinal(2).unwrap().name_str(), "uint32_t");
+    assert_eq!(til.get_type_by_ordinal(4).unwrap().name_str(), "integer_t");
+    assert!(til.get_type_by_ordinal(0xFFFF).is_none());
+}
+
+#[test]
+fn test_complex_type_classification() {
+    use binrw::BinReaderExt;
+    use idb_parser::TypeKind;
+
+    let kind = |bytes: &[u8]| {
+        let mut cursor = binrw::io::Cursor::new(bytes.to_vec());
+        cursor.read_ne::<idb_parser::Types>().unwrap().kind()
+    };
+
+    // BT_COMPLEX with each BTMT_* flag; the const/volatile modifier bits
+    // (0x40/0x80) must not change the classification
+    for modifier in [0x00, 0x40, 0x80] {
+        assert_eq!(kind(&[0x0D | modifier, 0x09, 0x07]), TypeKind::Struct);
+        assert_eq!(kind(&[0x1D | modifier, 0x09, 0x07]), TypeKind::Union);
+        assert_eq!(kind(&[0x2D | modifier, 0x02, 0x82, 0x05]), TypeKind::Enum);
+        assert_eq!(kind(&[0x3D | modifier, 0x02, b'A']), TypeKind::Typedef);
+    }
+}
+
+#[test]
+fn test_extended_member_count() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // DT 0x7FFE followed by DE 0x8000: 4096 members, no alignment
+    let mut struct_bytes = vec![0x0D, 0xFF, 0xFF, 0x84, 0x80, 0x00];
+    struct_bytes.resize(struct_bytes.len() + 4096, 0x07);
+    let mut union_bytes = struct_bytes.clone();
+    union_bytes[0] = 0x1D;
+
+    let mut cursor = binrw::io::Cursor::new(struct_bytes);
+    match cursor.read_ne::<Types>().unwrap() {
+        Types::Struct(struct_type) => assert_eq!(struct_type.members.len(), 4096),
+        other => panic!("expected a struct, got {:?}", other),
+    }
+    let mut cursor = binrw::io::Cursor::new(union_bytes);
+    match cursor.read_ne::<Types>().unwrap() {
+        Types::Union(union_type) => assert_eq!(union_type.members.len(), 4096),
+        other => panic!("expected a union, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_function_arglocs() {
+    use binrw::BinReaderExt;
+    use idb_parser::{ArgLoc, Types};
+
+    // int __usercall f@<eax>(int@<ecx>, int@<edx:ebx>, int, int@<esi+4>)
+    let bytes = vec![
+        0x0C, 0xF0, // BT_FUNC, CM_CC_SPECIAL
+        0x07, 0x01, // int, in register 0
+        0x05, // 4 args
+        0x07, 0x02, // int, in register 1
+        0x07, 0x83, 0x03, // int, in registers 3 and 2
+        0x07, 0xFF, 0x02, 0x08, // int, ALOC_STACK at 8
+        0x07, 0xFF, 0x06, 0x07, 0x04, // int, ALOC_RREL register 6 + 4
+    ];
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let function = match cursor.read_ne::<Types>().unwrap() {
+        Types::Function(function) => function,
+        other => panic!("expected a function, got {:?}", other),
+    };
+    assert_eq!(function.ret_loc, Some(ArgLoc::Reg1(0)));
+    let locs = function
+        .args
+        .iter()
+        .map(|arg| arg.1.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        locs,
+        vec![
+            Some(ArgLoc::Reg1(1)),
+            Some(ArgLoc::Reg2(3 | 2 << 16)),
+            Some(ArgLoc::Stack(8)),
+            Some(ArgLoc::RRel { reg: 6, offset: 4 }),
+        ]
+    );
+}
+
+#[test]
+fn test_function_truncated_args() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // int __cdecl f(int, char) declaring three arguments
+    let bytes = vec![0x0C, 0x30, 0x07, 0x04, 0x07, 0x32];
+    let err = binrw::io::Cursor::new(bytes)
+        .read_ne::<Types>()
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("function declares 3 arguments but the type ends after 2"));
+
+    // A special calling convention losing the last argument's location
+    let bytes = vec![0x0C, 0xF0, 0x07, 0x01, 0x02, 0x07];
+    assert!(binrw::io::Cursor::new(bytes).read_ne::<Types>().is_err());
+}
+
+#[test]
+fn test_function_spoiled_registers() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // Three spoiled registers, the last with its size in a separate byte,
+    // then int __cdecl f(int)
+    let bytes = vec![0x0C, 0xA3, 0x01, 0x13, 0x85, 0x04, 0x30, 0x07, 0x02, 0x07];
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let function = match cursor.read_ne::<Types>().unwrap() {
+        Types::Function(function) => function,
+        other => panic!("expected a function, got {:?}", other),
+    };
+    assert_eq!(function.spoiled, vec![0x01, 0x13, 0x85]);
+    assert_eq!(function.attributes, 0);
+    assert_eq!(function.args.len(), 1);
+    assert_eq!(cursor.position(), 10);
+
+    // A count of 15 carries the noreturn attribute instead of registers
+    let bytes = vec![0x0C, 0xAF, 0x01, 0x30, 0x07, 0x02, 0x07];
+    let function = match binrw::io::Cursor::new(bytes).read_ne::<Types>().unwrap() {
+        Types::Function(function) => function,
+        other => panic!("expected a function, got {:?}", other),
+    };
+    assert!(function.spoiled.is_empty());
+    assert_eq!(function.attributes, 0x01);
+    assert_eq!(function.args.len(), 1);
+}
+
+#[test]
+fn test_serialize_dt_round_trip() {
+    use binrw::BinReaderExt;
+
+    assert_eq!(idb_parser::serialize_dt(0), vec![0x01]);
+    assert_eq!(idb_parser::serialize_dt(126), vec![0x7F]);
+    assert_eq!(idb_parser::serialize_dt(127), vec![0x80, 0x01]);
+    assert_eq!(idb_parser::serialize_dt(0x7FFE), vec![0xFF, 0xFF]);
+    for value in 0..=0x7FFE {
+        let bytes = idb_parser::serialize_dt(value);
+        let len = bytes.len() as u64;
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        assert_eq!(cursor.read_ne::<idb_parser::DT>().unwrap().0, value);
+        assert_eq!(cursor.position(), len);
+    }
+}
+
+#[test]
+fn test_type_attribute_strings() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // Pointer with a TAH carrying one attribute string "abc" = [1, 2], to int
+    let bytes = vec![
+        0x0A, 0xFE, 0x10, 0x02, 0x04, b'a', b'b', b'c', 0x03, 0x01, 0x02, 0x07,
+    ];
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let pointer = match cursor.read_ne::<Types>().unwrap() {
+        Types::Pointer(pointer) => pointer,
+        other => panic!("expected a pointer, got {:?}", other),
+    };
+    assert_eq!(pointer.tah.0 .0, 0x10);
+    assert_eq!(pointer.tah.0 .1, vec![("abc".to_string(), vec![1, 2])]);
+    assert!(matches!(pointer.typ, Types::Unset(_)));
+    assert_eq!(cursor.position(), 12);
+}
+
+#[test]
+fn test_based_array() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    // BT_ARRAY without BTMT_NONBASED: four DA bytes, then base 0x12 with 3 elements, of int
+    let mut cursor = binrw::io::Cursor::new(vec![0x0B, 0x80, 0x80, 0x80, 0x81, 0x32, 0x07]);
+    let array = match cursor.read_ne::<Types>().unwrap() {
+        Types::Array(array) => array,
+        other => panic!("expected an array, got {:?}", other),
+    };
+    assert!(!array.is_non_based);
+    assert_eq!(array.base, 0x12);
+    assert_eq!(array.nelem, 3);
+    assert!(matches!(array.elem_type, Types::Unset(_)));
+    assert_eq!(cursor.position(), 7);
+}
+
+#[test]
+fn test_function_calling_convention() {
+    use binrw::BinReaderExt;
+    use idb_parser::{CallingConvention, Types};
+
+    for (cc, convention) in [
+        (0x30, CallingConvention::Cdecl),
+        (0x40, CallingConvention::Cdecl),
+        (0x50, CallingConvention::Stdcall),
+        (0x60, CallingConvention::Pascal),
+        (0x70, CallingConvention::Fastcall),
+        (0x80, CallingConvention::Thiscall),
+    ] {
+        // int f(char *) with the given calling convention byte
+        let mut cursor = binrw::io::Cursor::new(vec![0x0C, cc, 0x07, 0x02, 0x0A, 0x32]);
+        match cursor.read_ne::<Types>().unwrap() {
+            Types::Function(function) => assert_eq!(function.calling_convention(), convention),
+            other => panic!("expected a function, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_struct_layout() {
+    use binrw::BinReaderExt;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let catalog = til.catalog();
+
+    let layout = til
+        .struct_layout(catalog.resolve("segment_command_64").unwrap())
+        .unwrap();
+    let expected = [
+        ("cmd", 0, 4),
+        ("cmdsize", 4, 4),
+        ("segname", 8, 16),
+        ("vmaddr", 24, 8),
+        ("vmsize", 32, 8),
+        ("fileoff", 40, 8),
+        ("filesize", 48, 8),
+        ("maxprot", 56, 4),
+        ("initprot", 60, 4),
+        ("nsects", 64, 4),
+        ("flags", 68, 4),
+    ];
+    assert_eq!(layout.len(), expected.len());
+    for ((name, offset, size), (expected_name, expected_offset, expected_size)) in
+        layout.iter().zip(expected)
+    {
+        assert_eq!(
+            (name.as_str(), *offset, *size),
+            (expected_name, expected_offset, expected_size)
+        );
+    }
+
+    // Members typed with undefined base TIL typedefs can't be laid out
+    assert!(til
+        .struct_layout(catalog.resolve("__UNICODE_STRING").unwrap())
+        .is_none());
+    assert!(til
+        .struct_layout(catalog.resolve("uint32_t").unwrap())
+        .is_none());
+
+    // BT_UNK | BTMT_SIZE128 is _UNKNOWN with no size, BT_VOID | BTMT_SIZE128
+    // is the 16 byte _OWORD
+    let basic = |byte: u8| {
+        binrw::io::Cursor::new([byte])
+            .read_ne::<idb_parser::Types>()
+            .unwrap()
+    };
+    assert_eq!(basic(0x30).size(&til), None);
+    assert_eq!(basic(0x31).size(&til), Some(16));
+}
+
+#[test]
+fn test_type_size() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let parse = |bytes: Vec<u8>| {
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        cursor.read_ne::<Types>().unwrap()
+    };
+
+    // int *, with the near pointer size gcc.til's memory model selects
+    assert_eq!(parse(vec![0x0A, 0x07]).size(&til), Some(4));
+    // int[16]
+    assert_eq!(parse(vec![0x1B, 0x11, 0x07]).size(&til), Some(64));
+    // void has no size
+    assert_eq!(parse(vec![0x01]).size(&til), None);
+
+    let catalog = til.catalog();
+    let size = |name: &str| catalog.resolve(name).unwrap().tinfo.size(&til);
+    assert_eq!(size("segment_command_64"), Some(72));
+    // dylib_command nests struct dylib, which holds the lc_str union
+    assert_eq!(size("dylib"), Some(16));
+    assert_eq!(size("dylib_command"), Some(24));
+    assert_eq!(size("gcc_va_list"), None);
+}
+
+#[test]
+fn test_sizeof_basic() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    // int, BT_INT with no flags, follows the section's size_i
+    assert_eq!(til.sizeof_basic(0x07, 0x00), Some(4));
+    assert_eq!(til.sizeof_basic(0x07, 0x20), Some(4));
+    // long long, BT_INT64
+    assert_eq!(til.sizeof_basic(0x05, 0x00), Some(8));
+    // double, then long double, BT_FLOAT | BTMT_LNGDBL, which gcc.til
+    // doesn't give a size
+    assert_eq!(til.sizeof_basic(0x09, 0x10), Some(8));
+    assert_eq!(til.sizeof_basic(0x09, 0x20), None);
+    // With size_ldbl patched in as in test_til_extended_sizes
+    let mut bytes = TIL.to_vec();
+    let flags = u32::from_le_bytes(bytes[10..14].try_into().unwrap()) | 0x0004 | 0x0100;
+    bytes[10..14].copy_from_slice(&flags.to_le_bytes());
+    let def_align = 14 + 1 + bytes[14] as usize;
+    let def_align = def_align + 1 + bytes[def_align] as usize + 5;
+    bytes.splice(def_align + 1..def_align + 1, [2, 8, 8, 16]);
+    let extended = idb_parser::TILSection::parse(&bytes).unwrap();
+    assert_eq!(extended.sizeof_basic(0x09, 0x20), Some(16));
+    // Only the low bits of each argument count
+    assert_eq!(til.sizeof_basic(0x32, 0x12), Some(1));
+    // BT_UNK: _WORD, _QWORD, then _UNKNOWN of no particular size
+    assert_eq!(til.sizeof_basic(0x00, 0x00), None);
+    assert_eq!(til.sizeof_basic(0x00, 0x10), Some(2));
+    assert_eq!(til.sizeof_basic(0x00, 0x20), Some(8));
+    assert_eq!(til.sizeof_basic(0x00, 0x30), None);
+    // BT_VOID: void, _BYTE, _DWORD, then _OWORD
+    assert_eq!(til.sizeof_basic(0x01, 0x00), None);
+    assert_eq!(til.sizeof_basic(0x01, 0x10), Some(1));
+    assert_eq!(til.sizeof_basic(0x01, 0x20), Some(4));
+    assert_eq!(til.sizeof_basic(0x01, 0x30), Some(16));
+}
+
+#[test]
+fn test_id0_pages() {
+    use idb_parser::Page;
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let id0 = idb.id0.unwrap();
+    assert_eq!(id0.page_size, 0x2000);
+    // Page 0 holds the b-tree header, pages 2 and 6 are on the free list
+    assert_eq!(id0.pages.len(), 12);
+    assert!(id0.pages[0].is_none());
+    assert_eq!(id0.pages.iter().flatten().count(), id0.page_count as usize);
+    assert!(id0.pages[id0.root_page as usize].is_some());
+    let records = id0
+        .pages
+        .iter()
+        .flatten()
+        .map(|page| match page {
+            Page::Leaf(entries) => entries.len(),
+            Page::Branch { entries, .. } => entries.len(),
+        })
+        .sum::<usize>();
+    assert_eq!(records, id0.record_count as usize);
+
+    // A leaf claiming two entries that holds only part of the first
+    assert!(Page::parse(&[0, 0, 0, 0, 2, 0, 0, 0, 0, 0]).is_err());
+    // A record offset past the end of the page
+    let mut page = vec![0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0xF0, 0];
+    assert!(Page::parse(&page).is_err());
+    // A record whose key length runs past the end of the page
+    page[10] = 12;
+    page.extend_from_slice(&[0xFF, 0]);
+    assert!(Page::parse(&page).is_err());
+    page[12] = 1;
+    page.extend_from_slice(&[b'k', 1, 0, b'v']);
+    match Page::parse(&page).unwrap() {
+        Page::Leaf(entries) => {
+            assert_eq!(entries[0].key, b"k");
+            assert_eq!(entries[0].value, b"v");
+        }
+        Page::Branch { .. } => unreachable!(),
+    }
+}
+
+#[test]
+fn test_id0_get() {
+    use idb_parser::Page;
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let id0 = idb.id0.unwrap();
+
+    assert_eq!(id0.get(b"$ MAX LINK"), Some(&[0_u8; 8][..]));
+    // The name of the node at 0x100000000, the Mach-O header
+    assert_eq!(
+        id0.get(b".\x00\x00\x00\x01\x00\x00\x00\x00N"),
+        Some(&b"__mh_execute_header"[..])
+    );
+    // Keys stored in the root index page itself
+    match id0.pages[id0.root_page as usize].as_ref().unwrap() {
+        Page::Branch { entries, .. } => {
+            for pointer in entries {
+                assert_eq!(
+                    id0.get(&pointer.entry.key),
+                    Some(pointer.entry.value.as_slice())
+                );
+            }
+        }
+        Page::Leaf(_) => unreachable!(),
+    }
+    assert_eq!(id0.get(b"$ MAX LINKS"), None);
+    assert_eq!(id0.get(b""), None);
+}
+
+#[test]
+fn test_id0_entries() {
+    use idb_parser::Page;
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let id0 = idb.id0.unwrap();
+    let keys = id0
+        .entries()
+        .map(|entry| entry.key.as_slice())
+        .collect::<Vec<_>>();
+
+    assert_eq!(keys.len(), id0.record_count as usize);
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(keys[0], b"$ MAX LINK");
+
+    // Page 3 turned into a branch pointing back into the tree
+    let mut bytes = IDB.to_vec();
+    bytes[0x6107] = 0x7F;
+    let id0 = idb_parser::IDB::parse(&bytes).unwrap().id0.unwrap();
+    let stored = id0
+        .pages
+        .iter()
+        .flatten()
+        .map(|page| match page {
+            Page::Leaf(entries) => entries.len(),
+            Page::Branch { entries, .. } => entries.len(),
+        })
+        .sum::<usize>();
+    assert!(id0.entries().count() <= stored);
+}
+
+#[test]
+fn test_id0_scan_prefix() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let id0 = idb.id0.unwrap();
+
+    // The special netnodes' names all start with "$ "
+    let special = id0.scan_prefix(b"$ ").collect::<Vec<_>>();
+    let expected = id0
+        .entries()
+        .filter(|entry| entry.key.starts_with(b"$ "))
+        .collect::<Vec<_>>();
+    assert!(!special.is_empty());
+    assert_eq!(special, expected);
+    assert_eq!(special[0].key, b"$ MAX LINK");
+
+    // A prefix in the middle of the tree and one no key has
+    let names = id0.scan_prefix(b"N").count();
+    assert_eq!(
+        names,
+        id0.entries().filter(|entry| entry.key[0] == b'N').count()
+    );
+    assert!(names > 0);
+    assert_eq!(id0.scan_prefix(b"$ zzz").count(), 0);
+    assert_eq!(id0.scan_prefix(b"").count(), id0.record_count as usize);
+}
+
+#[test]
+fn test_netnode() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let id0 = idb.id0.unwrap();
+    let netnode = idb_parser::Netnode::new(&id0, 8).unwrap();
+
+    // The Mach-O header at 0x100000000
+    assert_eq!(
+        netnode.name(0x1_0000_0000),
+        Some(&b"__mh_execute_header"[..])
+    );
+    assert_eq!(
+        netnode.supval(0x1_0000_0000, 0),
+        Some(&b"Magic number\0"[..])
+    );
+    assert_eq!(netnode.altval(0x1_0000_0000, 8), Some(0x0208_0804));
+    assert_eq!(netnode.altval(0x1_0000_0000, 9), None);
+    // A data reference from 0x100003E6E to the string at 0x100003F90
+    assert_eq!(netnode.charval(0x1_0000_3F90, 0x1_0000_3E6E, b'D'), Some(1));
+    assert_eq!(netnode.name(0xFF00_0000_0000_0002), Some(&b"Root Node"[..]));
+
+    // Only 32 and 64 bit node ids exist
+    assert!(idb_parser::Netnode::new(&id0, 4).is_some());
+    for word_size in [0, 2, 9, 16] {
+        assert!(idb_parser::Netnode::new(&id0, word_size).is_none());
+    }
+}
+
+#[test]
+fn test_image_base() {
+    // gcc is a Mach-O executable loaded at 0x100000000
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    assert_eq!(idb.image_base(), Some(0x1_0000_0000));
+    let without_id0 = idb_parser::IdbParser::new().skip_id0().parse(IDB).unwrap();
+    assert_eq!(without_id0.image_base(), None);
+}
+
+#[test]
+fn test_id1_flags() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let id1 = idb.id1.unwrap();
+    let bounds = id1
+        .segments
+        .iter()
+        .map(|segment| (segment.start, segment.end))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bounds,
+        [
+            (0x1_0000_0000, 0x1_0000_8018),
+            (0x1_0000_8020, 0x1_0000_8038)
+        ]
+    );
+
+    // `push rbp` at the start of a function
+    assert_eq!(id1.flags_at(0x1_0000_3DA0), Some(0x1000_5755));
+    assert!(idb_parser::is_code(id1.flags_at(0x1_0000_3DA0).unwrap()));
+    assert_eq!(id1.flags_at(0x1_0000_8017), Some(0x100));
+    assert_eq!(id1.flags_at(0x1_0000_8018), None);
+    assert_eq!(id1.flags_at(0xFFFF_FFFF), None);
+}
+
+#[test]
+fn test_byte_flags() {
+    use idb_parser::ByteFlags;
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let id1 = idb.id1.unwrap();
+    let flags = |ea| ByteFlags(id1.flags_at(ea).unwrap());
+
+    // The named and commented dword at the start of the Mach-O header
+    let header = flags(0x1_0000_0000);
+    assert!(header.is_data() && !header.is_code());
+    assert!(header.has_name() && header.has_comment());
+    assert_eq!(header.data_size(), Some(4));
+    let tail = flags(0x1_0000_0001);
+    assert!(tail.is_tail() && !tail.is_data() && !tail.has_name());
+    // Commented but unnamed
+    let data = flags(0x1_0000_0004);
+    assert!(data.is_data() && data.has_comment() && !data.has_name());
+    let code = flags(0x1_0000_3DA0);
+    assert!(code.is_code() && !code.is_tail() && !code.is_unknown());
+    let unknown = flags(0x1_0000_8017);
+    assert!(unknown.is_unknown() && !unknown.is_code());
+
+    let flagged = id1.iter_flagged().collect::<Vec<_>>();
+    assert_eq!(flagged.len(), 0x8018 + 0x18);
+    assert_eq!(flagged[0], (0x1_0000_0000, header));
+    assert_eq!(flagged[0x8018].0, 0x1_0000_8020);
+    assert!(flagged
+        .iter()
+        .all(|&(ea, flags)| id1.flags_at(ea) == Some(flags.0)));
+    assert_eq!(
+        flagged
+            .iter()
+            .filter(|(ea, flags)| (0x1_0000_3DA0..0x1_0000_3DA8).contains(ea) && flags.is_code())
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn test_segments() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let table = idb.seg.as_ref().unwrap();
+    assert_eq!(
+        table.segments,
+        idb_parser::SEGSection::from_id0(idb.id0.as_ref().unwrap(), 8)
+            .unwrap()
+            .segments
+    );
+    let segments = &table.segments;
+    assert_eq!(segments.len(), 10);
+    assert!(segments.windows(2).all(|pair| pair[0].end <= pair[1].start));
+    assert!(segments.iter().all(|segment| segment.start < segment.end));
+
+    let text = table.segment_at(0x1_0000_3DA0).unwrap();
+    assert_eq!((text.start, text.end), (0x1_0000_3DA0, 0x1_0000_3F5E));
+    assert_eq!(
+        (text.name.as_str(), text.class.as_str()),
+        ("__text", "CODE")
+    );
+    assert_eq!((text.perm, text.bitness), (5, 2));
+    assert_eq!(text.permissions_str(), "R-X");
+    assert_eq!(text.type_str(), "CODE");
+    assert_eq!(table.segment_at(0x1_0000_3F5D), Some(text));
+    assert_eq!(table.segment_at(0x1_0000_0000).unwrap().name, "HEADER");
+    let extern_segment = segments.last().unwrap();
+    assert_eq!(extern_segment.class, "XTRN");
+    assert_eq!(extern_segment.type_str(), "XTRN");
+    assert_eq!(segments[0].type_str(), "DATA");
+    assert_eq!(
+        (extern_segment.start, extern_segment.end),
+        (0x1_0000_8020, 0x1_0000_8038)
+    );
+    // Between __data and the extern segment
+    assert_eq!(table.segment_at(0x1_0000_8018), None);
+    assert_eq!(table.segment_at(0x1_0000_8038), None);
+
+    // Through the IDB, inside __text and in the gap before the extern segment
+    assert_eq!(
+        idb.segment_containing(0x1_0000_3E00).unwrap().name,
+        "__text"
+    );
+    assert_eq!(idb.segment_containing(0x1_0000_8018), None);
+
+    // The table lives in id0, there is nothing to read it from without it
+    let parser = idb_parser::IdbParser::new();
+    assert!(parser.skip_id0().parse(IDB).unwrap().seg.is_none());
+    assert!(parser.skip_seg().parse(IDB).unwrap().seg.is_none());
+}
+
+#[test]
+fn test_fixups() {
+    use idb_parser::Fixup;
+
+    // The pointers into the extern segment, one in __got and two in
+    // __la_symbol_ptr
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let fixups = idb.id0.as_ref().unwrap().fixups();
+    let fixup = |ea, target| Fixup {
+        ea,
+        kind: 0x18,
+        target,
+    };
+    assert_eq!(
+        fixups,
+        vec![
+            fixup(0x1_0000_4000, 0x1_0000_8020),
+            fixup(0x1_0000_8000, 0x1_0000_8028),
+            fixup(0x1_0000_8008, 0x1_0000_8030),
+        ]
+    );
+    let table = idb.seg.as_ref().unwrap();
+    assert!(fixups
+        .iter()
+        .all(|fixup| table.segment_at(fixup.target).unwrap().class == "XTRN"));
+}
+
+#[test]
+fn test_idb_header_versions() {
+    use idb_parser::IDBError;
+
+    // A version 4 header with 32 bit offsets, holding only the sample's TIL section
+    let mut bytes = b"IDA1\0\0".to_vec();
+    for offset in [0_u32, 0, 0, 0, 44] {
+        bytes.extend(offset.to_le_bytes());
+    }
+    bytes.extend(0xAABBCCDD_u32.to_le_bytes());
+    bytes.extend(4_u16.to_le_bytes());
+    bytes.extend([0; 12]);
+    assert_eq!(bytes.len(), 44);
+    bytes.extend(&IDB[0x40117..]);
+    let old = idb_parser::IDB::parse(&bytes).unwrap();
+    assert!(old.id0.is_none() && old.id1.is_none());
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    assert_eq!(
+        old.til.unwrap().iter_types().count(),
+        idb.til.unwrap().iter_types().count()
+    );
+
+    for version in [0_u16, 5, 7] {
+        let mut bytes = IDB.to_vec();
+        bytes[0x1E..0x20].copy_from_slice(&version.to_le_bytes());
+        let err = idb_parser::IDB::parse(&bytes).unwrap_err();
+        assert_eq!(
+            err.root_cause().custom_err::<IDBError>(),
+            Some(&IDBError::UnsupportedVersion(version))
+        );
+    }
+}
+
+#[test]
+fn test_parse_32_bit_idb() {
+    // A 32 bit database whose ID1 section covers 0x401000..0x401004
+    let mut id1 = b"VA*\0".to_vec();
+    for word in [3_u32, 1, 0x800, 2, 0x401000, 0x401004] {
+        id1.extend(word.to_le_bytes());
+    }
+    id1.resize(0x2000, 0);
+    for flags in [0x10005755_u32, 0x10748, 0x2003e5, 0x100] {
+        id1.extend(flags.to_le_bytes());
+    }
+
+    let mut bytes = b"IDA1\0\0".to_vec();
+    for offset in [0_u32, 44, 0, 0, 0] {
+        bytes.extend(offset.to_le_bytes());
+    }
+    bytes.extend(0xAABBCCDD_u32.to_le_bytes());
+    bytes.extend(4_u16.to_le_bytes());
+    bytes.extend([0; 12]);
+    bytes.push(0);
+    bytes.extend((id1.len() as u64).to_le_bytes());
+    bytes.extend(&id1);
+
+    let idb = idb_parser::IDB::parse(&bytes).unwrap();
+    assert!(!idb.is_64());
+    assert_eq!(idb.word_size(), 4);
+    let id1 = idb.id1.unwrap();
+    assert_eq!(id1.segments[0].start, 0x401000);
+    assert_eq!(id1.segments[0].end, 0x401004);
+    assert!(idb_parser::is_code(id1.flags_at(0x401000).unwrap()));
+    assert_eq!(id1.flags_at(0x401003), Some(0x100));
+    assert_eq!(id1.flags_at(0x401004), None);
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    assert!(idb.is_64());
+    assert_eq!(idb.word_size(), 8);
+}
+
+#[test]
+fn test_verify_checksums() {
+    use idb_parser::IDBSectionKind;
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    assert_eq!(idb.verify_checksums(IDB), Ok(()));
+
+    // The last byte of the NAM section
+    let mut bytes = IDB.to_vec();
+    bytes[0x40116] ^= 1;
+    assert_eq!(idb.verify_checksums(&bytes), Err(IDBSectionKind::NAM));
+    // Inside the TIL section
+    let mut bytes = IDB.to_vec();
+    bytes[0x40117 + 9 + 0x100] ^= 0x80;
+    assert_eq!(idb.verify_checksums(&bytes), Err(IDBSectionKind::TIL));
+    // Truncated inside the ID1 section
+    assert_eq!(
+        idb.verify_checksums(&IDB[..0x20000]),
+        Err(IDBSectionKind::ID1)
+    );
+}
+
+#[test]
+fn test_parse_from_reader() {
+    let resource = |name| format!("{}/tests/resources/{}", env!("CARGO_MANIFEST_DIR"), name);
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let from_cursor = idb_parser::IDB::parse_from_reader(std::io::Cursor::new(IDB)).unwrap();
+    let from_file =
+        idb_parser::IDB::parse_from_reader(std::fs::File::open(resource("gcc.i64")).unwrap())
+            .unwrap();
+    assert_eq!(format!("{:?}", from_cursor), format!("{:?}", idb));
+    assert_eq!(format!("{:?}", from_file), format!("{:?}", idb));
+    let from_path = idb_parser::IDB::parse_from_file(resource("gcc.i64")).unwrap();
+    assert_eq!(format!("{:?}", from_path), format!("{:?}", idb));
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let from_cursor = idb_parser::TILSection::parse_from_reader(std::io::Cursor::new(TIL)).unwrap();
+    let from_path = idb_parser::TILSection::parse_from_file(resource("gcc.til")).unwrap();
+    assert_eq!(format!("{:?}", from_cursor), format!("{:?}", til));
+    assert_eq!(format!("{:?}", from_path), format!("{:?}", til));
+
+    // A borrowed reader stays usable, positioned past the parsed buckets
+    let mut reader = std::io::Cursor::new(TIL);
+    let from_borrowed = idb_parser::TILSection::parse_from_reader(&mut reader).unwrap();
+    assert_eq!(format!("{:?}", from_borrowed), format!("{:?}", til));
+    assert!(reader.position() > 0);
+}
+
+#[test]
+fn test_empty_ref() {
+    use binrw::BinReaderExt;
+    use idb_parser::{Ref, Types};
+
+    // A zero length ref
+    let mut cursor = binrw::io::Cursor::new(vec![0x01]);
+    assert!(cursor.read_ne::<Ref>().is_err());
+    // A ref holding only the '=' marker
+    let mut cursor = binrw::io::Cursor::new(vec![0x02, b'=']);
+    assert!(cursor.read_ne::<Ref>().is_err());
+    // Struct, union and enum type refs with nothing behind them
+    for typ in [0x0D, 0x1D, 0x2D] {
+        let mut cursor = binrw::io::Cursor::new(vec![typ, 0x01, 0x01]);
+        assert!(cursor.read_ne::<Types>().is_err());
+    }
+
+    // A ref to a named type still parses
+    let mut cursor = binrw::io::Cursor::new(vec![0x05, 0x3D, 0x03, b'#', 0x01]);
+    assert!(matches!(
+        cursor.read_ne::<Ref>(),
+        Ok(Ref(Types::Typedef(_)))
+    ));
+}
+
+#[test]
+fn test_truncated_bucket() {
+    use binrw::BinReaderExt;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let types = types_bucket(&til);
+    assert_eq!(types.ndefs_mismatch(), None);
+    let data = types_data(&til);
+
+    // The bucket claims every entry but holds only half the data, followed
+    // by bytes that aren't part of it
+    let half = &data[..data.len() / 2];
+    let mut bucket = types.ndefs.to_le_bytes().to_vec();
+    bucket.extend_from_slice(&(half.len() as u32).to_le_bytes());
+    bucket.extend_from_slice(half);
+    bucket.extend_from_slice(&data[data.len() / 2..]);
+
+    let mut cursor = binrw::io::Cursor::new(&bucket);
+    let truncated = cursor
+        .read_ne_args::<idb_parser::TILBucket>(
+            idb_parser::TILBucketBinReadArgs::builder()
+                .size_e(4)
+                .depth(TypeDepth::default())
+                .finalize(),
+        )
+        .unwrap();
+    assert_eq!(cursor.position() as usize, 8 + half.len());
+    let parsed = truncated.type_info.len();
+    assert!(parsed > 0 && parsed < types.type_info.len());
+    assert_eq!(truncated.ndefs_mismatch(), Some((types.ndefs, parsed)));
+    assert!(truncated
+        .type_info
+        .iter()
+        .zip(&types.type_info)
+        .all(|(truncated, original)| truncated.name_str() == original.name_str()));
+
+    // No data at all
+    let mut cursor = binrw::io::Cursor::new([1, 0, 0, 0, 0, 0, 0, 0]);
+    let empty = cursor
+        .read_ne_args::<idb_parser::TILBucket>(
+            idb_parser::TILBucketBinReadArgs::builder()
+                .size_e(4)
+                .depth(TypeDepth::default())
+                .finalize(),
+        )
+        .unwrap();
+    assert_eq!(empty.ndefs_mismatch(), Some((1, 0)));
+
+    // A whole bucket whose mach_header_64 struct has a member count DT of
+    // zero is malformed, not short, and fails
+    let mut data = data;
+    let name = b"mach_header_64\0";
+    let entry = data
+        .windows(name.len())
+        .position(|window| window == name)
+        .unwrap();
+    let tinfo = entry + name.len() + 4;
+    assert_eq!(data[tinfo], 0x0D);
+    data[tinfo + 1] = 0;
+    let mut bucket = types.ndefs.to_le_bytes().to_vec();
+    bucket.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bucket.extend_from_slice(&data);
+    let err = binrw::io::Cursor::new(&bucket)
+        .read_ne_args::<idb_parser::TILBucket>(
+            idb_parser::TILBucketBinReadArgs::builder()
+                .size_e(4)
+                .depth(TypeDepth::default())
+                .finalize(),
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("DT value of zero"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_types_to_json() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let json = til.to_json().unwrap();
+    let types: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let types = types.as_array().unwrap();
+    assert_eq!(types.len(), til.iter_types().count());
+
+    let segment = types
+        .iter()
+        .find(|info| info["name"] == "segment_command_64")
+        .unwrap();
+    let original = til
+        .iter_types()
+        .find(|info| info.name_str() == "segment_command_64")
+        .unwrap();
+    assert_eq!(segment["ordinal"], original.ordinal.value());
+    assert_eq!(segment["fields"][0], "cmd");
+    assert_eq!(
+        segment["fields"].as_array().unwrap().len(),
+        original.fields.0.len()
+    );
+    let members = segment["tinfo"]["Struct"]["members"].as_array().unwrap();
+    assert_eq!(members.len(), original.fields.0.len());
+}
+
+#[test]
+fn test_c_header() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let header = til.to_c_header();
+    let position = |declaration: &str| {
+        header
+            .find(declaration)
+            .unwrap_or_else(|| panic!("{:?} missing from\n{}", declaration, header))
+    };
+    let order = [
+        "typedef int integer_t;",
+        "typedef integer_t cpu_type_t;",
+        "struct mach_header_64 {\n  uint32_t magic;\n  cpu_type_t cputype;",
+        "union lc_str {",
+        "struct dylinker_command {",
+        "struct dylib {\n  union lc_str name;",
+        "struct dylib_command {",
+        "struct String {\n  char *content;",
+        "struct Data {\n  struct String **strings;",
+        "struct Structure {",
+        "struct __UNICODE_STRING {",
+        "typedef struct __UNICODE_STRING AUNICODE_STRING;",
+    ];
+    assert!(order
+        .windows(2)
+        .all(|pair| position(pair[0]) < position(pair[1])));
+    assert!(position("typedef unsigned int uint32_t;") < position("struct mach_header_64 {"));
+    assert!(header.contains("  char segname[16];\n"));
+    assert!(header.contains("  struct Structure *prev;\n"));
+    assert!(header.contains("typedef __va_list_tag gcc_va_list[1];\n"));
+    assert_eq!(header.matches("struct segment_command_64 {").count(), 1);
+    assert_eq!(
+        header
+            .lines()
+            .filter(|line| line.starts_with("typedef") || line.ends_with('{'))
+            .count(),
+        til.iter_types().count()
+    );
+
+    for (bytes, declaration) in [
+        (vec![0x0A, 0x1B, 0x05, 0x07], "int (*name)[4]"),
+        (
+            vec![0x0A, 0x0C, 0x30, 0x07, 0x02, 0x07],
+            "int (__cdecl *name)(int a1)",
+        ),
+        (
+            vec![0x0A, 0x0C, 0x40, 0x01, 0x02, 0x4A, 0x32],
+            "void (__cdecl *name)(char *const a1, ...)",
+        ),
+        (
+            vec![0x1B, 0x03, 0x0A, 0x0C, 0x20, 0x01, 0x01],
+            "void (__cdecl *name[2])(void)",
+        ),
+        // BTMT_SIZE128 of BT_UNK, then of BT_VOID
+        (vec![0x30], "_UNKNOWN name"),
+        (vec![0x31], "_OWORD name"),
+    ] {
+        let mut cursor = binrw::io::Cursor::new(bytes);
+        let typ = cursor.read_ne::<Types>().unwrap();
+        assert_eq!(til.c_declaration(&typ, "name"), declaration);
+    }
+
+    // typedef struct A *PA; struct A { PA next; }; with the typedef first,
+    // a cycle only the forward declaration of struct A can break
+    let ndefs = types_bucket(&til).ndefs;
+    let mut entries = Vec::new();
+    for (name, ordinal, tinfo, fields) in [
+        ("PA", ndefs + 1, &[0x0A, 0x3D, 0x02, b'A'][..], &b""[..]),
+        (
+            "A",
+            ndefs + 2,
+            &[0x0D, 0x09, 0x3D, 0x03, b'P', b'A'],
+            b"\x05next",
+        ),
+    ] {
+        entries.extend_from_slice(&0_u32.to_le_bytes());
+        entries.extend_from_slice(name.as_bytes());
+        entries.push(0);
+        entries.extend_from_slice(&ordinal.to_le_bytes());
+        entries.extend_from_slice(tinfo);
+        entries.extend_from_slice(&[0, 0]);
+        entries.extend_from_slice(fields);
+        entries.extend_from_slice(&[0, 0, 0]);
+    }
+    let til = idb_parser::TILSection::parse(&til_with_types(&entries, 2)).unwrap();
+    let header = til.to_c_header();
+    let position = |declaration: &str| {
+        header
+            .find(declaration)
+            .unwrap_or_else(|| panic!("{:?} missing from\n{}", declaration, header))
+    };
+    assert!(position("struct A;\n") < position("typedef struct A *PA;\n"));
+    assert!(position("typedef struct A *PA;\n") < position("struct A {\n  PA next;\n};\n"));
+}
+
+#[test]
+fn test_lazy_idb() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let lazy = idb_parser::LazyIDB::new(IDB, false).unwrap();
+    assert_eq!(
+        format!("{:?}", lazy.til().unwrap()),
+        format!("{:?}", idb.til.as_ref())
+    );
+    assert!(std::ptr::eq(
+        lazy.til().unwrap().unwrap(),
+        lazy.til().unwrap().unwrap()
+    ));
+    assert_eq!(
+        lazy.seg().unwrap().unwrap().segments,
+        idb.seg.as_ref().unwrap().segments
+    );
+
+    // Clobber the id0 section header, only the id0 accessor should notice.
+    let mut bytes = IDB.to_vec();
+    bytes[0xfc..0xfc + 9].fill(0xff);
+    let lazy = idb_parser::LazyIDB::new(&bytes, false).unwrap();
+    assert_eq!(
+        lazy.til().unwrap().unwrap().iter_types().count(),
+        idb.til.unwrap().iter_types().count()
+    );
+    assert!(lazy.id1().unwrap().is_some());
+    assert!(lazy.id0().is_err());
+    assert!(idb_parser::LazyIDB::new(&bytes, true).is_err());
+    assert!(idb_parser::LazyIDB::new(IDB, true)
+        .unwrap()
+        .id0()
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn test_type_lookup_index() {
+    let mut til = idb_parser::TILSection::parse(TIL).unwrap();
+    let names: Vec<String> = til
+        .iter_types()
+        .map(|info| info.name_str().into_owned())
+        .collect();
+    let ordinals: Vec<u64> = til.iter_types().map(|info| info.ordinal.value()).collect();
+
+    let debug = format!("{:?}", til);
+    for (name, &ordinal) in names.iter().zip(&ordinals) {
+        assert_eq!(til.get_type(name).unwrap().name_str(), *name);
+        assert_eq!(
+            til.get_type_by_ordinal(ordinal).unwrap().ordinal.value(),
+            ordinal
+        );
+    }
+    // Building the index doesn't show
+    assert_eq!(format!("{:?}", til), debug);
+    assert!(til.get_type("no_such_type").is_none());
+    assert!(til.get_type_by_ordinal(1000).is_none());
+
+    match &mut til.types {
+        TILBucketType::Default(def) => def.type_info[0].ordinal = idb_parser::TILOrdinal::U32(100),
+        TILBucketType::Zip(zip) => zip.type_info[0].ordinal = idb_parser::TILOrdinal::U32(100),
+    }
+    assert!(til.get_type_by_ordinal(100).is_none());
+    // Entries that moved are still found by scanning the bucket
+    match &mut til.types {
+        TILBucketType::Default(def) => def.type_info.swap(0, 1),
+        TILBucketType::Zip(zip) => zip.type_info.swap(0, 1),
+    }
+    assert_eq!(til.get_type(&names[1]).unwrap().name_str(), names[1]);
+    assert_eq!(
+        til.get_type_by_ordinal(ordinals[1])
+            .unwrap()
+            .ordinal
+            .value(),
+        ordinals[1]
+    );
+    til.invalidate_index();
+    assert_eq!(til.get_type_by_ordinal(100).unwrap().name_str(), names[0]);
+    assert!(til.get_type_by_ordinal(ordinals[0]).is_none());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_parse_from_mmap() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/resources/gcc.i64");
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let mapped = idb_parser::IDB::parse_from_mmap(&path).unwrap();
+    assert_eq!(format!("{:?}", mapped), format!("{:?}", idb));
+    assert!(idb_parser::IDB::parse_from_mmap(&path.with_extension("missing")).is_err());
+}
+
+#[test]
+fn test_bitfield_struct() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let bytes = vec![
+        0x0D, 0x21, // 4 members, no alignment
+        0x2E, 0x08, // 32 bit storage, width 3, unsigned
+        0x2E, 0x0B, // 32 bit storage, width 5, signed
+        0x3E, 0x52, // 64 bit storage, width 40, unsigned
+        0x32, // char
+    ];
+    let mut cursor = binrw::io::Cursor::new(bytes);
+    let typ = cursor.read_ne::<Types>().unwrap();
+    assert_eq!(cursor.position(), 9);
+    let bitfields = match &typ {
+        Types::Struct(struct_type) => struct_type
+            .members
+            .iter()
+            .filter_map(|member| match &member.0 {
+                Types::Bitfield(bitfield) => {
+                    Some((bitfield.nbytes, bitfield.width, bitfield.unsigned))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("expected a struct, got {:?}", other),
+    };
+    assert_eq!(bitfields, vec![(4, 3, true), (4, 5, false), (8, 40, true)]);
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    assert_eq!(
+        til.c_declaration(&typ, "s"),
+        "struct {\n  unsigned int field_0 : 3;\n  int field_1 : 5;\n  \
+         unsigned __int64 field_2 : 40;\n  char field_3;\n} s"
+    );
+}
+
+#[test]
+fn test_type_comments() {
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let types = types_bucket(&til);
+    assert!(types
+        .type_info
+        .iter()
+        .all(|info| info.comment().is_empty() && info.field_comment(0).is_none()));
+
+    // Append struct point { int x; int y; } with a type comment and a
+    // comment on its first field only
+    let mut entry = 0_u32.to_le_bytes().to_vec();
+    entry.extend_from_slice(b"point\0");
+    entry.extend_from_slice(&(types.ndefs + 1).to_le_bytes());
+    entry.extend_from_slice(&[0x0D, 0x11, 0x07, 0x07, 0x00]);
+    entry.extend_from_slice(b"A point\nin the plane\0");
+    entry.extend_from_slice(b"\x02x\x02y\0");
+    entry.extend_from_slice(b"\x0Dx coordinate\x01\0");
+    entry.push(0);
+
+    let til = idb_parser::TILSection::parse(&til_with_types(&entry, 1)).unwrap();
+    let point = til.get_type("point").unwrap();
+    assert_eq!(point.comment(), "A point\nin the plane");
+    assert_eq!(point.field_comment(0).as_deref(), Some("x coordinate"));
+    assert_eq!(point.field_comment(1), None);
+    assert_eq!(point.field_comment(2), None);
+    assert!(til.to_c_header().contains(
+        "// A point\n// in the plane\nstruct point {\n  int x; // x coordinate\n  int y;\n};\n"
+    ));
+}
+
+#[test]
+fn test_reserved_type() {
+    use binrw::BinReaderExt;
+    use idb_parser::{TypeKind, Types};
+
+    let read = |bytes: &[u8]| {
+        let mut cursor = binrw::io::Cursor::new(bytes.to_vec());
+        let typ = cursor.read_ne::<Types>().unwrap();
+        (typ, cursor.position())
+    };
+
+    // Directly, with modifier bits, and as a pointer target: one byte each time
+    for byte in [0x0F, 0x4F, 0x8F] {
+        let (typ, len) = read(&[byte, 0x07]);
+        assert!(matches!(typ, Types::Reserved(metadata) if metadata.0 == byte));
+        assert_eq!(len, 1);
+    }
+    let (typ, len) = read(&[0x0A, 0x0F, 0x07]);
+    match typ {
+        Types::Pointer(pointer) => assert_eq!(pointer.typ.kind(), TypeKind::Reserved),
+        other => panic!("expected a pointer, got {:?}", other),
+    }
+    assert_eq!(len, 2);
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let (typ, _) = read(&[0x0F]);
+    assert_eq!(typ.kind(), TypeKind::Reserved);
+    assert_eq!(typ.size(&til), None);
+    assert_eq!(til.c_declaration(&typ, "x"), "_UNKNOWN x");
+}
+
+#[test]
+fn test_malformed_input_errors() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let read = |bytes: Vec<u8>| binrw::io::Cursor::new(bytes).read_ne::<Types>();
+    // A struct whose member count DT is zero
+    let err = read(vec![0x0D, 0x00]).unwrap_err();
+    assert!(err.to_string().contains("DT value of zero"));
+    // A function whose type attribute never ends
+    let err = read(vec![0x0C, 0x30, 0xFE, 0x80, 0x80, 0x80, 0x80, 0x01]).unwrap_err();
+    assert!(err.to_string().contains("invalid type attribute"));
+
+    // Corrupted type bytes inside the types bucket and a truncated bucket
+    // are reported rather than aborting
+    for (pos, value) in [(0x6D, 0xFE), (0x9F, 0x00), (0xDE, 0x80), (0xE0, 0x3D)] {
+        let mut bytes = TIL.to_vec();
+        bytes[pos] = value;
+        assert!(
+            idb_parser::TILSection::parse(&bytes).is_err(),
+            "corrupting {:#x} went unnoticed",
+            pos
+        );
+    }
+    assert!(idb_parser::TILSection::parse(&TIL[..TIL.len() - 16]).is_err());
+}
+
+#[test]
+fn test_truncated_input_errors() {
+    use binrw::BinReaderExt;
+
+    let bucket = |bytes: &[u8]| {
+        binrw::io::Cursor::new(bytes).read_ne_args::<idb_parser::TILBucket>(
+            idb_parser::TILBucketBinReadArgs::builder()
+                .size_e(4)
+                .depth(TypeDepth::default())
+                .finalize(),
+        )
+    };
+    assert!(bucket(&[]).is_err());
+    assert!(bucket(&[1, 0, 0, 0]).is_err());
+    // A length far past the end of the input
+    let err = bucket(&[1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0x07]).unwrap_err();
+    assert!(err.to_string().contains("only 0x1 remain"));
+
+    assert!(idb_parser::TILSection::parse(&[]).is_err());
+    assert!(idb_parser::IDB::parse(&[]).is_err());
+    assert!(idb_parser::IDB::parse(&IDB[..0x1000]).is_err());
+
+    // An ID1 segment claiming the whole address space
+    let mut bytes = IDB.to_vec();
+    let bounds = 0x18105 + 9 + 20;
+    bytes[bounds + 8..bounds + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+    let err = idb_parser::IDB::parse(&bytes).unwrap_err();
+    assert!(err.to_string().contains("is too large"));
+
+    // An ID0 record count nothing is reserved for
+    let count = idb_parser::IDB::parse(IDB)
+        .unwrap()
+        .id0
+        .unwrap()
+        .entries()
+        .count();
+    let mut bytes = IDB.to_vec();
+    bytes[0x10F..0x113].copy_from_slice(&u32::MAX.to_le_bytes());
+    let id0 = idb_parser::IDB::parse(&bytes).unwrap().id0.unwrap();
+    assert_eq!(id0.record_count, u32::MAX);
+    assert_eq!(id0.entries().count(), count);
+
+    // A zipped bucket declaring far more than it holds
+    let mut bytes = vec![1, 0, 0, 0];
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    bytes.extend_from_slice(&[0x78, 0x9c]);
+    assert!(binrw::io::Cursor::new(&bytes)
+        .read_ne_args::<TILBucketType>((true, 4, false, TypeDepth::default(), true))
+        .is_err());
+}
+
+#[test]
+fn test_id2_section() {
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    assert!(idb.id2.is_none());
+
+    // Point the version 6 header's id2 offset at the ID0 b-tree, then at
+    // the ID1 section which isn't one
+    let with_id2 = |offset: u64| {
+        let mut bytes = IDB.to_vec();
+        bytes[76..84].copy_from_slice(&offset.to_le_bytes());
+        bytes
+    };
+    let bytes = with_id2(0xFC);
+    let id2 = idb_parser::IDB::parse(&bytes).unwrap().id2.unwrap();
+    assert!(id2.is_present());
+    assert!(id2.btree().is_some());
+    assert!(id2.raw().is_none());
+    assert!(id2.entries().eq(idb.id0.as_ref().unwrap().entries()));
+    let lazy = idb_parser::LazyIDB::new(&bytes, false).unwrap();
+    assert_eq!(
+        lazy.id2().unwrap().unwrap().entries().count(),
+        id2.entries().count()
+    );
+
+    let id2 = idb_parser::IDB::parse(&with_id2(0x18105))
+        .unwrap()
+        .id2
+        .unwrap();
+    assert!(id2.is_present());
+    assert!(id2.btree().is_none());
+    assert_eq!(id2.entries().count(), 0);
+    assert_eq!(&id2.raw().unwrap()[..4], b"VA*\0");
+
+    // A section of its own past the others, a zlib compressed b-tree, then
+    // an empty body
+    let with_section = |compression: u8, body: &[u8]| {
+        let mut bytes = with_id2(IDB.len() as u64);
+        bytes.push(compression);
+        bytes.extend((body.len() as u64).to_le_bytes());
+        bytes.extend(body);
+        bytes
+    };
+    let lazy = idb_parser::LazyIDB::new(IDB, false).unwrap();
+    let btree = lazy.raw_section(idb_parser::IDBSectionKind::ID0).unwrap();
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(btree, 6);
+    let id2 = idb_parser::IDB::parse(&with_section(2, &compressed))
+        .unwrap()
+        .id2
+        .unwrap();
+    assert!(id2.entries().eq(idb.id0.as_ref().unwrap().entries()));
+    let id2 = idb_parser::IDB::parse(&with_section(0, &[]))
+        .unwrap()
+        .id2
+        .unwrap();
+    assert!(!id2.is_present());
+    assert_eq!(id2.raw(), Some(&[][..]));
+}
+
+#[test]
+fn test_raw_sections() {
+    use idb_parser::IDBSectionKind;
+
+    let lazy = idb_parser::LazyIDB::new(IDB, false).unwrap();
+    let header = lazy.section_header(IDBSectionKind::ID0).unwrap();
+    assert_eq!(header.compression_method, 0);
+    let id0 = lazy.raw_section(IDBSectionKind::ID0).unwrap();
+    assert_eq!(id0.len() as u64, header.section_length);
+    assert_eq!(&id0[19..28], b"B-tree v2");
+    assert_eq!(
+        &lazy.raw_section(IDBSectionKind::ID1).unwrap()[..4],
+        b"VA*\0"
+    );
+    assert!(lazy
+        .raw_section(IDBSectionKind::TIL)
+        .unwrap()
+        .starts_with(b"IDATIL"));
+    assert!(lazy.section_header(IDBSectionKind::SEG).is_none());
+    assert!(lazy.raw_section(IDBSectionKind::ID2).is_none());
+
+    // A section running past the end of the buffer
+    let lazy = idb_parser::LazyIDB::new(&IDB[..0x20000], false).unwrap();
+    assert!(lazy.raw_section(IDBSectionKind::ID0).is_some());
+    assert!(lazy.raw_section(IDBSectionKind::ID1).is_none());
+}
+
+#[test]
+fn test_compressed_sections() {
+    use idb_parser::{CompressionMethod, IDBError, IDBSectionKind};
+
+    // Appends a zlib copy of a section and points the header at it
+    let recompress = |kind: IDBSectionKind, header_offset: usize| {
+        let lazy = idb_parser::LazyIDB::new(IDB, false).unwrap();
+        let body = lazy.raw_section(kind).unwrap();
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(body, 6);
+        let mut bytes = IDB.to_vec();
+        let offset = bytes.len() as u64;
+        bytes.push(2);
+        bytes.extend((compressed.len() as u64).to_le_bytes());
+        bytes.extend(&compressed);
+        bytes[header_offset..header_offset + 8].copy_from_slice(&offset.to_le_bytes());
+        bytes
+    };
+
+    let idb = idb_parser::IDB::parse(IDB).unwrap();
+    let lazy = idb_parser::LazyIDB::new(IDB, false).unwrap();
+    let header = lazy.section_header(IDBSectionKind::ID0).unwrap();
+    assert_eq!(header.compression(), Ok(CompressionMethod::None));
+    assert_eq!(idb.id0.as_ref().unwrap().header(), header);
+
+    let bytes = recompress(IDBSectionKind::ID0, 6);
+    let lazy = idb_parser::LazyIDB::new(&bytes, false).unwrap();
+    let header = lazy.section_header(IDBSectionKind::ID0).unwrap();
+    assert_eq!(header.compression(), Ok(CompressionMethod::Zlib));
+    let zlib = idb_parser::IDB::parse(&bytes).unwrap();
+    assert_eq!(zlib.id0.as_ref().unwrap().header(), header);
+    assert!(zlib
+        .id0
+        .unwrap()
+        .entries()
+        .eq(idb.id0.as_ref().unwrap().entries()));
+
+    let bytes = recompress(IDBSectionKind::TIL, 48);
+    let zlib = idb_parser::IDB::parse(&bytes).unwrap();
+    assert_eq!(
+        zlib.til.unwrap().iter_types().count(),
+        idb.til.as_ref().unwrap().iter_types().count()
+    );
+    let lazy = idb_parser::LazyIDB::new(&bytes, false).unwrap();
+    assert_eq!(
+        format!("{:?}", lazy.til().unwrap().unwrap().types),
+        format!("{:?}", idb.til.as_ref().unwrap().types)
+    );
+
+    let mut bytes = IDB.to_vec();
+    bytes[0xfc] = 5;
+    let err = idb_parser::IDB::parse(&bytes).unwrap_err();
+    assert_eq!(
+        err.root_cause().custom_err::<IDBError>(),
+        Some(&IDBError::UnsupportedCompression(5))
+    );
+}
+
+#[test]
+fn test_float_widths() {
+    use binrw::BinReaderExt;
+    use idb_parser::Types;
+
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let declaration = |byte: u8| {
+        let typ = binrw::io::Cursor::new(vec![byte])
+            .read_ne::<Types>()
+            .unwrap();
+        (til.c_declaration(&typ, "x"), typ.size(&til))
+    };
+    // BT_FLOAT with BTMT_FLOAT, BTMT_DOUBLE, BTMT_LNGDBL and BTMT_SPECFLT
+    assert_eq!(declaration(0x09), ("float x".to_string(), Some(4)));
+    assert_eq!(declaration(0x19), ("double x".to_string(), Some(8)));
+    assert_eq!(declaration(0x29), ("long double x".to_string(), None));
+    assert_eq!(declaration(0x39), ("_TBYTE x".to_string(), None));
+    // The modifier bits don't change the width
+    assert_eq!(declaration(0x59), ("const double x".to_string(), Some(8)));
+    assert_eq!(declaration(0x89), ("volatile float x".to_string(), Some(4)));
+}
+
+#[test]
+fn test_c_declaration_matches_header() {
+    use idb_parser::TypeKind;
+
+    // Typedefs in the header are spelled exactly as c_declaration spells them
+    let til = idb_parser::TILSection::parse(TIL).unwrap();
+    let header = til.to_c_header();
+    let mut checked = 0;
+    for info in til.iter_types() {
+        if matches!(
+            info.tinfo.kind(),
+            TypeKind::Struct | TypeKind::Union | TypeKind::Enum
+        ) {
+            continue;
+        }
+        let declaration = til.c_declaration(&info.tinfo, &info.name_str());
+        assert!(
+            header.contains(&format!("typedef {};", declaration)),
+            "{} missing from the header",
+            declaration
+        );
+        checked += 1;
+    }
+    assert!(checked > 0);
 }